@@ -7,7 +7,8 @@ export enum Code {
 	Validation = 'errors.validation',
 	ProjectsGitAuth = 'errors.projects.git.auth',
 	DefaultTargetNotFound = 'errors.projects.default_target.not_found',
-	CommitSigningFailed = 'errors.commit.signing_failed'
+	CommitSigningFailed = 'errors.commit.signing_failed',
+	DuplicateBranchName = 'errors.branch.duplicate_name'
 }
 
 export class UserError extends Error {
@@ -26,6 +26,7 @@ export class Project {
 	omit_certificate_check: boolean | undefined;
 	use_diff_context: boolean | undefined;
 	snapshot_lines_threshold!: number | undefined;
+	sensitive_data_patterns: string[] | undefined;
 	use_new_locking!: boolean;
 
 	get vscodePath() {
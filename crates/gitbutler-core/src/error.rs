@@ -132,6 +132,7 @@ pub enum Code {
     ProjectGitAuth,
     DefaultTargetNotFound,
     CommitSigningFailed,
+    DuplicateBranchName,
 }
 
 impl std::fmt::Display for Code {
@@ -142,6 +143,7 @@ impl std::fmt::Display for Code {
             Code::ProjectGitAuth => "errors.projects.git.auth",
             Code::DefaultTargetNotFound => "errors.projects.default_target.not_found",
             Code::CommitSigningFailed => "errors.commit.signing_failed",
+            Code::DuplicateBranchName => "errors.branch.duplicate_name",
         };
         f.write_str(code)
     }
@@ -13,7 +13,7 @@ use crate::git::diff::FileDiff;
 use crate::virtual_branches::integration::{
     GITBUTLER_INTEGRATION_COMMIT_AUTHOR_EMAIL, GITBUTLER_INTEGRATION_COMMIT_AUTHOR_NAME,
 };
-use crate::virtual_branches::Branch;
+use crate::virtual_branches::{Branch, VirtualBranchesState};
 use crate::{git::diff::hunks_by_filepath, git::RepositoryExt, projects::Project};
 
 use super::{
@@ -455,10 +455,9 @@ impl Project {
         repo.checkout_tree(workdir_tree.as_object(), Some(&mut checkout_builder))?;
 
         // Update virtual_branches.toml with the state from the snapshot
-        fs::write(
-            repo.path().join("gitbutler").join("virtual_branches.toml"),
-            vb_toml_blob.content(),
-        )?;
+        let snapshot_state = VirtualBranchesState::from_toml(from_utf8(vb_toml_blob.content())?)
+            .context("failed to read virtual_branches.toml of snapshot")?;
+        self.virtual_branches().replace_state(&snapshot_state)?;
 
         // reset the repo index to our index tree
         let index_tree_entry = snapshot_tree
@@ -784,8 +783,7 @@ fn tree_from_applied_vbranches(
         .find_blob(vb_toml_entry.id())
         .context("failed to convert virtual_branches tree entry to blob")?;
 
-    let vbs_from_toml: crate::virtual_branches::VirtualBranchesState =
-        toml::from_str(from_utf8(vb_toml_blob.content())?)?;
+    let vbs_from_toml = VirtualBranchesState::from_toml(from_utf8(vb_toml_blob.content())?)?;
     let applied_branch_trees: Vec<git2::Oid> = vbs_from_toml
         .branches
         .values()
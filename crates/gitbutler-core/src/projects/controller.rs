@@ -135,6 +135,14 @@ impl Controller {
             }
         }
 
+        if let Some(patterns) = &project.sensitive_data_patterns {
+            for pattern in patterns {
+                if let Err(err) = regex::Regex::new(pattern) {
+                    bail!("invalid sensitive data pattern \"{pattern}\": {err}");
+                }
+            }
+        }
+
         // FIXME(qix-): On windows, we have to force to system executable.
         // FIXME(qix-): This is a hack for now, and will be smoothed over in the future.
         #[cfg(windows)]
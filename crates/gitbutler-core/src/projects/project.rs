@@ -3,9 +3,15 @@ use std::{
     time,
 };
 
+use anyhow::{Context, Result};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 
-use crate::{id::Id, types::default_true::DefaultTrue, virtual_branches::VirtualBranchesHandle};
+use crate::{
+    id::Id,
+    types::default_true::DefaultTrue,
+    virtual_branches::{branch::default_sensitive_data_patterns, VirtualBranchesHandle},
+};
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
@@ -88,6 +94,9 @@ pub struct Project {
     pub omit_certificate_check: Option<bool>,
     // The number of changed lines that will trigger a snapshot
     pub snapshot_lines_threshold: Option<usize>,
+    /// Regular expressions of secrets to look for in commits, replacing the default ones if set.
+    #[serde(default)]
+    pub sensitive_data_patterns: Option<Vec<String>>,
 
     #[serde(default = "default_true")]
     pub use_new_locking: bool,
@@ -124,4 +133,18 @@ impl Project {
     pub fn snapshot_lines_threshold(&self) -> usize {
         self.snapshot_lines_threshold.unwrap_or(20)
     }
+
+    /// Compiles the configured sensitive data patterns, or the default ones if none are configured.
+    pub fn sensitive_data_patterns(&self) -> Result<Vec<Regex>> {
+        match &self.sensitive_data_patterns {
+            Some(patterns) => patterns
+                .iter()
+                .map(|pattern| {
+                    Regex::new(pattern)
+                        .context(format!("invalid sensitive data pattern {pattern:?}"))
+                })
+                .collect(),
+            None => Ok(default_sensitive_data_patterns()),
+        }
+    }
 }
@@ -28,6 +28,7 @@ pub struct UpdateRequest {
     pub omit_certificate_check: Option<bool>,
     pub use_diff_context: Option<bool>,
     pub snapshot_lines_threshold: Option<usize>,
+    pub sensitive_data_patterns: Option<Vec<String>>,
     pub use_new_locking: Option<bool>,
 }
 
@@ -123,6 +124,10 @@ impl Storage {
             project.snapshot_lines_threshold = Some(snapshot_lines_threshold);
         }
 
+        if let Some(sensitive_data_patterns) = &update_request.sensitive_data_patterns {
+            project.sensitive_data_patterns = Some(sensitive_data_patterns.clone());
+        }
+
         if let Some(use_new_locking) = update_request.use_new_locking {
             project.use_new_locking = use_new_locking;
         }
@@ -256,6 +256,9 @@ pub fn set_base_branch(
                 order: 0,
                 selected_for_changes: None,
                 allow_rebasing: project_repository.project().ok_with_force_push.into(),
+                checkpoints: vec![],
+                ownership_log: vec![],
+                source_refname: None,
             };
 
             vb_state.set_branch(branch)?;
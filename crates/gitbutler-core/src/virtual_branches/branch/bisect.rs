@@ -0,0 +1,137 @@
+use std::{
+    path::Path,
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+
+use super::{commits_since_target, Branch};
+use crate::virtual_branches::target::Target;
+
+impl Branch {
+    /// Bisects the commits of this branch that are not yet part of `target` and returns
+    /// the first one for which `test_command` fails, or `None` if the branch head passes.
+    ///
+    /// Every candidate is checked out into a throw-away directory, so neither the workspace
+    /// nor the index is touched. A run exceeding `timeout_secs` is killed and counts as a failure.
+    pub fn find_regression_commit(
+        &self,
+        repo: &git2::Repository,
+        target: &Target,
+        test_command: &str,
+        timeout_secs: u64,
+    ) -> Result<Option<git2::Oid>> {
+        let mut candidates = commits_since_target(repo, self.head, target)?;
+        candidates.reverse();
+
+        let Some(&head) = candidates.last() else {
+            return Ok(None);
+        };
+        let timeout = Duration::from_secs(timeout_secs);
+        if test_passes_at(repo, head, test_command, timeout)? {
+            return Ok(None);
+        }
+
+        // everything before `low` passes, `candidates[high]` fails
+        let (mut low, mut high) = (0, candidates.len() - 1);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if test_passes_at(repo, candidates[mid], test_command, timeout)? {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        Ok(Some(candidates[high]))
+    }
+}
+
+fn test_passes_at(
+    repo: &git2::Repository,
+    commit_id: git2::Oid,
+    test_command: &str,
+    timeout: Duration,
+) -> Result<bool> {
+    let worktree = tempfile::tempdir().context("failed to create bisect worktree")?;
+    let tree = repo
+        .find_commit(commit_id)
+        .context("failed to find commit")?
+        .tree()
+        .context("failed to find tree")?;
+    repo.checkout_tree(
+        tree.as_object(),
+        Some(
+            git2::build::CheckoutBuilder::new()
+                .target_dir(worktree.path())
+                .update_index(false)
+                .force(),
+        ),
+    )
+    .context(format!("failed to check out {commit_id}"))?;
+
+    let mut child = shell_command(test_command, worktree.path())
+        .spawn()
+        .context(format!("failed to spawn {test_command:?}"))?;
+    let started_at = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status.success());
+        }
+        if started_at.elapsed() >= timeout {
+            tracing::warn!(%commit_id, test_command, "test command timed out");
+            kill_process_tree(&mut child);
+            return Ok(false);
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn shell_command(command: &str, cwd: &Path) -> Command {
+    #[cfg(windows)]
+    let mut cmd = {
+        use std::os::windows::process::CommandExt;
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+        cmd
+    };
+    #[cfg(not(windows))]
+    let mut cmd = {
+        use std::os::unix::process::CommandExt;
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        // lets `kill_process_tree()` reach everything the command starts
+        cmd.process_group(0);
+        cmd
+    };
+    cmd.current_dir(cwd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    cmd
+}
+
+/// Kills `child` along with all processes it started, like the workers of a test runner.
+fn kill_process_tree(child: &mut Child) {
+    let pid = child.id().to_string();
+    #[cfg(windows)]
+    let killed = {
+        use std::os::windows::process::CommandExt;
+        Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &pid])
+            .creation_flags(0x08000000) // CREATE_NO_WINDOW
+            .status()
+    };
+    // the child leads its own process group, see `shell_command()`
+    #[cfg(not(windows))]
+    let killed = Command::new("kill")
+        .args(["-s", "KILL", "--", &format!("-{pid}")])
+        .status();
+    if let Err(err) = killed {
+        tracing::warn!(?err, %pid, "failed to kill test command process tree");
+    }
+    child.kill().ok();
+    child.wait().ok();
+}
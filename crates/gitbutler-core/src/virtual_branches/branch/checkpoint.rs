@@ -0,0 +1,166 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{deserialize_u128, serialize_u128, Branch};
+use crate::{
+    id::Id,
+    virtual_branches::integration::{
+        GITBUTLER_INTEGRATION_COMMIT_AUTHOR_EMAIL, GITBUTLER_INTEGRATION_COMMIT_AUTHOR_NAME,
+    },
+};
+
+pub type CheckpointId = Id<Checkpoint>;
+
+/// The state of a branch at a point in time, which it can be restored to later.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Checkpoint {
+    pub id: CheckpointId,
+    pub label: Option<String>,
+    /// The head of the branch when the checkpoint was created.
+    #[serde(with = "crate::serde::oid")]
+    pub stack_head: git2::Oid,
+    /// A commit on top of `stack_head` holding the uncommitted changes of the branch,
+    /// or `None` if there were none.
+    #[serde(with = "crate::serde::oid_opt", default)]
+    pub wip_stash: Option<git2::Oid>,
+    #[serde(
+        serialize_with = "serialize_u128",
+        deserialize_with = "deserialize_u128"
+    )]
+    pub created_at_ms: u128,
+}
+
+impl Checkpoint {
+    /// The reference that keeps the commits of this checkpoint from being garbage collected.
+    ///
+    /// It lives outside of `refs/gitbutler/` so it isn't mistaken for a virtual branch.
+    pub fn refname(&self) -> String {
+        format!("refs/gitbutler-checkpoints/{}", self.id)
+    }
+}
+
+impl Branch {
+    /// Records the current head and uncommitted changes of this branch as a new checkpoint.
+    ///
+    /// The commits of the checkpoint are anchored by [`Checkpoint::refname()`], but the checkpoint
+    /// itself is only stored on `self`, so the branch has to be persisted afterwards.
+    pub fn create_checkpoint(
+        &mut self,
+        repo: &git2::Repository,
+        label: Option<&str>,
+    ) -> Result<Checkpoint> {
+        let head_commit = repo
+            .find_commit(self.head)
+            .context("failed to find branch head commit")?;
+
+        let wip_stash = if head_commit.tree_id() == self.tree {
+            None
+        } else {
+            let wip_tree = repo
+                .find_tree(self.tree)
+                .context("failed to find branch tree")?;
+            let signature = git2::Signature::now(
+                GITBUTLER_INTEGRATION_COMMIT_AUTHOR_NAME,
+                GITBUTLER_INTEGRATION_COMMIT_AUTHOR_EMAIL,
+            )?;
+            let message = format!(
+                "GitButler checkpoint of {}\n\n{}",
+                self.name,
+                label.unwrap_or_default()
+            );
+            let wip_commit_id = repo
+                .commit(
+                    None,
+                    &signature,
+                    &signature,
+                    message.trim_end(),
+                    &wip_tree,
+                    &[&head_commit],
+                )
+                .context("failed to write checkpoint commit")?;
+            Some(wip_commit_id)
+        };
+
+        let checkpoint = Checkpoint {
+            id: CheckpointId::generate(),
+            label: label.map(ToOwned::to_owned),
+            stack_head: self.head,
+            wip_stash,
+            created_at_ms: crate::time::now_ms(),
+        };
+        repo.reference(
+            &checkpoint.refname(),
+            wip_stash.unwrap_or(self.head),
+            true,
+            &format!("GitButler checkpoint of {}", self.name),
+        )
+        .context("failed to write checkpoint reference")?;
+        self.checkpoints.push(checkpoint.clone());
+        Ok(checkpoint)
+    }
+
+    /// Resets the head and uncommitted changes of this branch to those recorded in the
+    /// checkpoint with the given `id`.
+    ///
+    /// Only the branch state is changed, use
+    /// [`restore_branch_checkpoint()`](crate::virtual_branches::restore_branch_checkpoint)
+    /// to also update the workspace.
+    pub fn restore_checkpoint(&mut self, repo: &git2::Repository, id: CheckpointId) -> Result<()> {
+        let checkpoint = self
+            .checkpoints
+            .iter()
+            .find(|checkpoint| checkpoint.id == id)
+            .ok_or_else(|| anyhow!("checkpoint with ID {id} not found"))?;
+
+        let tree = repo
+            .find_commit(checkpoint.wip_stash.unwrap_or(checkpoint.stack_head))
+            .context("failed to find checkpoint commit")?
+            .tree_id();
+
+        self.head = checkpoint.stack_head;
+        self.tree = tree;
+        self.updated_timestamp_ms = crate::time::now_ms();
+        Ok(())
+    }
+
+    /// Removes the checkpoint with the given `id` along with its reference, so its commits
+    /// can be garbage collected.
+    ///
+    /// Only the branch state is changed, so the branch has to be persisted afterwards.
+    pub fn discard_checkpoint(&mut self, repo: &git2::Repository, id: CheckpointId) -> Result<()> {
+        let index = self
+            .checkpoints
+            .iter()
+            .position(|checkpoint| checkpoint.id == id)
+            .ok_or_else(|| anyhow!("checkpoint with ID {id} not found"))?;
+        delete_checkpoint_reference(repo, &self.checkpoints[index])?;
+        self.checkpoints.remove(index);
+        Ok(())
+    }
+
+    /// Removes all checkpoints of this branch along with their references.
+    ///
+    /// Only the branch state is changed, so the branch has to be persisted afterwards.
+    pub fn discard_checkpoints(&mut self, repo: &git2::Repository) -> Result<()> {
+        for checkpoint in &self.checkpoints {
+            delete_checkpoint_reference(repo, checkpoint)?;
+        }
+        self.checkpoints.clear();
+        Ok(())
+    }
+
+    /// Returns all checkpoints of this branch, oldest first.
+    pub fn list_checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+}
+
+fn delete_checkpoint_reference(repo: &git2::Repository, checkpoint: &Checkpoint) -> Result<()> {
+    match repo.find_reference(&checkpoint.refname()) {
+        Ok(mut reference) => reference
+            .delete()
+            .context("failed to delete checkpoint reference"),
+        Err(err) if err.code() == git2::ErrorCode::NotFound => Ok(()),
+        Err(err) => Err(err).context("failed to find checkpoint reference"),
+    }
+}
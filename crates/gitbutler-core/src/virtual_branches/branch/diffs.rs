@@ -0,0 +1,70 @@
+use anyhow::{Context, Result};
+
+use super::{commits_since_target, Branch};
+use crate::virtual_branches::target::Target;
+
+impl Branch {
+    /// Returns the patch from the merge base with `target` to the head of this branch,
+    /// as if all of its commits were squashed into one.
+    pub fn get_stacked_diff(
+        &self,
+        repo: &git2::Repository,
+        target: &Target,
+        context_lines: u32,
+    ) -> Result<String> {
+        let base_tree = self.get_base_tree(repo, target)?;
+        let head_tree = self.get_head_tree(repo)?;
+        patch_between(repo, Some(&base_tree), &head_tree, context_lines)
+    }
+
+    /// Returns the patch introduced by each commit of this branch that isn't part of `target`,
+    /// oldest commit first.
+    pub fn get_incremental_diffs(
+        &self,
+        repo: &git2::Repository,
+        target: &Target,
+        context_lines: u32,
+    ) -> Result<Vec<(git2::Oid, String)>> {
+        commits_since_target(repo, self.head, target)?
+            .into_iter()
+            .rev()
+            .map(|oid| -> Result<(git2::Oid, String)> {
+                let commit = repo.find_commit(oid)?;
+                let parent_tree = match commit.parent(0) {
+                    Ok(parent) => Some(parent.tree()?),
+                    Err(_) => None,
+                };
+                let patch =
+                    patch_between(repo, parent_tree.as_ref(), &commit.tree()?, context_lines)?;
+                Ok((commit.id(), patch))
+            })
+            .collect()
+    }
+}
+
+fn patch_between(
+    repo: &git2::Repository,
+    old_tree: Option<&git2::Tree>,
+    new_tree: &git2::Tree,
+    context_lines: u32,
+) -> Result<String> {
+    let mut diff_opts = git2::DiffOptions::new();
+    diff_opts
+        .show_binary(true)
+        .ignore_submodules(true)
+        .context_lines(context_lines);
+    let diff = repo
+        .diff_tree_to_tree(old_tree, Some(new_tree), Some(&mut diff_opts))
+        .context("failed to diff trees")?;
+
+    let mut patch = Vec::new();
+    diff.print(git2::DiffFormat::Patch, |_, _, line| {
+        if matches!(line.origin(), '+' | '-' | ' ') {
+            patch.push(line.origin() as u8);
+        }
+        patch.extend_from_slice(line.content());
+        true
+    })
+    .context("failed to print diff")?;
+    Ok(String::from_utf8_lossy(&patch).into_owned())
+}
@@ -1,14 +1,29 @@
+mod bisect;
+mod checkpoint;
+mod diffs;
 mod file_ownership;
 mod hunk;
 mod ownership;
+mod ownership_history;
+mod sensitive_data;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use bstr::ByteSlice;
+pub use checkpoint::{Checkpoint, CheckpointId};
 pub use file_ownership::OwnershipClaim;
 pub use hunk::{Hunk, HunkHash};
 pub use ownership::{reconcile_claims, BranchOwnershipClaims};
+pub use ownership_history::{OwnershipEntry, MAX_CLOSED_OWNERSHIP_ENTRIES};
+pub use sensitive_data::{
+    default_sensitive_data_patterns, SensitiveDataMatch, DEFAULT_SENSITIVE_DATA_PATTERNS,
+};
 use serde::{Deserialize, Serialize};
 
-use crate::{git, id::Id};
+use super::target::Target;
+use crate::{
+    git::{self, CommitExt},
+    id::Id,
+};
 
 pub type BranchId = Id<Branch>;
 
@@ -50,6 +65,16 @@ pub struct Branch {
     pub selected_for_changes: Option<i64>,
     #[serde(default = "default_true")]
     pub allow_rebasing: bool,
+    /// Points in time this branch can be restored to, oldest first.
+    #[serde(default)]
+    pub checkpoints: Vec<Checkpoint>,
+    /// When this branch started and stopped owning changes to files, oldest first.
+    #[serde(default)]
+    pub ownership_log: Vec<OwnershipEntry>,
+    /// The local branch whose commits were last brought into this branch, see
+    /// [`sync_branch_from_local_branch()`](crate::virtual_branches::sync_branch_from_local_branch).
+    #[serde(default)]
+    pub source_refname: Option<git::LocalRefname>,
 }
 
 fn default_true() -> bool {
@@ -72,10 +97,102 @@ where
     Ok(x)
 }
 
+fn serialize_u128_opt<S>(x: &Option<u128>, s: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    x.map(|x| x.to_string()).serialize(s)
+}
+
+fn deserialize_u128_opt<'de, D>(d: D) -> Result<Option<u128>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s = Option::<String>::deserialize(d)?;
+    s.map(|s| s.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// Returns the tree of the merge base between `a` and `b`.
+pub fn merge_base_tree(
+    repo: &git2::Repository,
+    a: git2::Oid,
+    b: git2::Oid,
+) -> Result<git2::Tree<'_>> {
+    let merge_base = repo.merge_base(a, b).context("failed to find merge base")?;
+    repo.find_commit(merge_base)
+        .context("failed to find merge base commit")?
+        .tree()
+        .context("failed to get base tree object")
+}
+
+/// Returns the commits reachable from `head` that aren't part of `target`, newest first.
+pub(crate) fn commits_since_target(
+    repo: &git2::Repository,
+    head: git2::Oid,
+    target: &Target,
+) -> Result<Vec<git2::Oid>> {
+    let mut revwalk = repo.revwalk().context("failed to create revwalk")?;
+    revwalk
+        .push(head)
+        .context(format!("failed to push {}", head))?;
+    revwalk
+        .hide(target.sha)
+        .context(format!("failed to hide {}", target.sha))?;
+    revwalk.set_sorting(git2::Sort::TOPOLOGICAL)?;
+    revwalk
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to walk commits")
+}
+
 impl Branch {
     pub fn refname(&self) -> git::VirtualRefname {
         self.into()
     }
+
+    /// Returns the tree of the merge base between the head of this branch and `target`.
+    pub fn get_base_tree<'a>(
+        &self,
+        repo: &'a git2::Repository,
+        target: &Target,
+    ) -> Result<git2::Tree<'a>> {
+        merge_base_tree(repo, target.sha, self.head)
+    }
+
+    /// Returns the tree of the head commit of this branch.
+    ///
+    /// Unlike [`Branch::tree`], this doesn't include uncommitted changes.
+    pub fn get_head_tree<'a>(&self, repo: &'a git2::Repository) -> Result<git2::Tree<'a>> {
+        repo.find_commit(self.head)
+            .context("failed to find branch head commit")?
+            .tree()
+            .context("failed to get head tree object")
+    }
+
+    /// Returns the ID and trimmed subject line of each commit of this branch that isn't part
+    /// of `target`, newest first.
+    pub fn get_commit_subjects(
+        &self,
+        repo: &git2::Repository,
+        target: &Target,
+    ) -> Result<Vec<(git2::Oid, String)>> {
+        commits_since_target(repo, self.head, target)?
+            .into_iter()
+            .map(|oid| -> Result<(git2::Oid, String)> {
+                let commit = repo.find_commit(oid)?;
+                let subject = commit
+                    .message_bstr()
+                    .lines()
+                    .next()
+                    .unwrap_or_default()
+                    .to_str_lossy()
+                    .trim()
+                    .to_string();
+                Ok((oid, subject))
+            })
+            .collect::<Result<Vec<_>>>()
+            .context("failed to collect commit subjects")
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
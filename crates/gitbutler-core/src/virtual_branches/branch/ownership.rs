@@ -143,7 +143,7 @@ pub fn reconcile_claims(
     for branch in &mut other_branches {
         let taken = new_claims
             .iter()
-            .flat_map(|c| branch.ownership.take(c))
+            .flat_map(|c| branch.unassign_hunk(c))
             .collect_vec();
         claim_outcomes.push(ClaimOutcome {
             updated_branch: branch.clone(),
@@ -0,0 +1,154 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    deserialize_u128, deserialize_u128_opt, serialize_u128, serialize_u128_opt, Branch, BranchId,
+    BranchOwnershipClaims, OwnershipClaim,
+};
+
+/// A period of time during which a branch owned changes to a file.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct OwnershipEntry {
+    pub file_path: PathBuf,
+    pub stack_id: BranchId,
+    #[serde(
+        serialize_with = "serialize_u128",
+        deserialize_with = "deserialize_u128"
+    )]
+    pub assigned_at_ms: u128,
+    /// `None` while the branch still owns changes to the file.
+    #[serde(
+        serialize_with = "serialize_u128_opt",
+        deserialize_with = "deserialize_u128_opt",
+        default
+    )]
+    pub unassigned_at_ms: Option<u128>,
+}
+
+/// Ownership that ends and starts again within this time is recorded as a single period, so
+/// hunks briefly changing hands during an operation or between status updates don't add entries.
+const OWNERSHIP_GAP_TOLERANCE_MS: u128 = 5_000;
+
+/// The number of finished ownership periods kept per branch. Older ones are dropped, as the
+/// log is stored with the branch state and in every snapshot of it.
+pub const MAX_CLOSED_OWNERSHIP_ENTRIES: usize = 50;
+
+impl Branch {
+    /// Claims the hunks in `claim` for this branch, recording when the branch started
+    /// owning changes to the file.
+    pub fn assign_hunk(&mut self, claim: OwnershipClaim) {
+        self.record_ownership_start(&claim.file_path);
+        self.ownership.put(claim);
+    }
+
+    /// Releases the hunks in `claim` from this branch and returns the claims that were taken.
+    ///
+    /// Once no changes to the file are owned anymore, the end of the ownership is recorded.
+    pub fn unassign_hunk(&mut self, claim: &OwnershipClaim) -> Vec<OwnershipClaim> {
+        let taken = self.ownership.take(claim);
+        let still_owned = self
+            .ownership
+            .claims
+            .iter()
+            .any(|owned| owned.file_path == claim.file_path);
+        if !still_owned {
+            self.record_ownership_end(&claim.file_path);
+        }
+        taken
+    }
+
+    /// Replaces all claims of this branch with `ownership`, recording the end of ownership for
+    /// files that aren't claimed anymore and the start for newly claimed ones.
+    pub fn replace_ownership(&mut self, ownership: BranchOwnershipClaims) {
+        let is_claimed_in = |claims: &BranchOwnershipClaims, file_path: &Path| {
+            claims
+                .claims
+                .iter()
+                .any(|claim| claim.file_path == file_path)
+        };
+        let released_files = self
+            .ownership
+            .claims
+            .iter()
+            .filter(|claim| !is_claimed_in(&ownership, &claim.file_path))
+            .map(|claim| claim.file_path.clone())
+            .collect::<Vec<_>>();
+        let claimed_files = ownership
+            .claims
+            .iter()
+            .filter(|claim| !is_claimed_in(&self.ownership, &claim.file_path))
+            .map(|claim| claim.file_path.clone())
+            .collect::<Vec<_>>();
+
+        for file_path in &released_files {
+            self.record_ownership_end(file_path);
+        }
+        for file_path in &claimed_files {
+            self.record_ownership_start(file_path);
+        }
+        self.ownership = ownership;
+    }
+
+    /// Returns the periods during which this branch owned changes to `file_path`, oldest first.
+    pub fn get_file_ownership_history(&self, file_path: &Path) -> Vec<OwnershipEntry> {
+        self.ownership_log
+            .iter()
+            .filter(|entry| entry.file_path == file_path)
+            .cloned()
+            .collect()
+    }
+
+    fn record_ownership_start(&mut self, file_path: &Path) {
+        let now = crate::time::now_ms();
+        let latest = self
+            .ownership_log
+            .iter_mut()
+            .rev()
+            .find(|entry| entry.file_path == file_path);
+        match latest {
+            Some(entry) if entry.unassigned_at_ms.is_none() => {}
+            Some(entry)
+                if entry
+                    .unassigned_at_ms
+                    .is_some_and(|end| now.saturating_sub(end) < OWNERSHIP_GAP_TOLERANCE_MS) =>
+            {
+                entry.unassigned_at_ms = None;
+            }
+            _ => self.ownership_log.push(OwnershipEntry {
+                file_path: file_path.to_owned(),
+                stack_id: self.id,
+                assigned_at_ms: now,
+                unassigned_at_ms: None,
+            }),
+        }
+    }
+
+    fn record_ownership_end(&mut self, file_path: &Path) {
+        let now = crate::time::now_ms();
+        let Some(entry) = self
+            .ownership_log
+            .iter_mut()
+            .rev()
+            .find(|entry| entry.file_path == file_path && entry.unassigned_at_ms.is_none())
+        else {
+            return;
+        };
+        entry.unassigned_at_ms = Some(now);
+
+        let closed = self
+            .ownership_log
+            .iter()
+            .filter(|entry| entry.unassigned_at_ms.is_some())
+            .count();
+        let mut excess = closed.saturating_sub(MAX_CLOSED_OWNERSHIP_ENTRIES);
+        self.ownership_log.retain(|entry| {
+            if excess > 0 && entry.unassigned_at_ms.is_some() {
+                excess -= 1;
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use bstr::ByteSlice;
+use regex::Regex;
+
+use super::{commits_since_target, Branch};
+use crate::virtual_branches::target::Target;
+
+/// Patterns for common secret formats, checked by default before pushing.
+pub const DEFAULT_SENSITIVE_DATA_PATTERNS: &[&str] = &[
+    // AWS access key IDs
+    r"\b(AKIA|ASIA)[0-9A-Z]{16}\b",
+    // AWS secret access keys
+    r#"(?i)aws_secret_access_key\s*[:=]\s*["']?[A-Za-z0-9/+=]{40}"#,
+    // PEM encoded private keys
+    r"-----BEGIN ([A-Z]+ )?PRIVATE KEY-----",
+    // GitHub tokens
+    r"\bgh[pousr]_[A-Za-z0-9]{36,}\b",
+    // Slack tokens
+    r"\bxox[abprs]-[A-Za-z0-9-]{10,}\b",
+    // Generic bearer tokens
+    r"(?i)\bbearer\s+[A-Za-z0-9\-._~+/]{20,}=*",
+];
+
+/// Compiles [`DEFAULT_SENSITIVE_DATA_PATTERNS`].
+pub fn default_sensitive_data_patterns() -> Vec<Regex> {
+    DEFAULT_SENSITIVE_DATA_PATTERNS
+        .iter()
+        .map(|pattern| Regex::new(pattern).expect("default patterns are valid"))
+        .collect()
+}
+
+/// A line added by a commit that matches one of the sensitive data patterns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SensitiveDataMatch {
+    pub commit_oid: git2::Oid,
+    pub file_path: PathBuf,
+    /// The 1-based line number in the file as of `commit_oid`.
+    pub line_number: u32,
+    /// The source of the pattern that matched.
+    pub pattern_name: String,
+}
+
+impl Branch {
+    /// Scans the lines added by each commit of this branch that isn't part of `target`
+    /// for matches of `patterns`, newest commit first.
+    pub fn scan_for_sensitive_data(
+        &self,
+        repo: &git2::Repository,
+        target: &Target,
+        patterns: &[Regex],
+    ) -> Result<Vec<SensitiveDataMatch>> {
+        let mut matches = Vec::new();
+        for oid in commits_since_target(repo, self.head, target)? {
+            let commit = repo.find_commit(oid).context("failed to find commit")?;
+            let parent_tree = match commit.parent(0) {
+                Ok(parent) => Some(parent.tree()?),
+                Err(_) => None,
+            };
+            let diff = repo
+                .diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)
+                .context("failed to diff commit")?;
+
+            diff.foreach(
+                &mut |_, _| true,
+                None,
+                None,
+                Some(&mut |delta, _hunk, line| {
+                    if line.origin() != '+' {
+                        return true;
+                    }
+                    let (Some(file_path), Some(line_number)) =
+                        (delta.new_file().path(), line.new_lineno())
+                    else {
+                        return true;
+                    };
+                    let content = line.content().to_str_lossy();
+                    for pattern in patterns.iter().filter(|p| p.is_match(&content)) {
+                        matches.push(SensitiveDataMatch {
+                            commit_oid: commit.id(),
+                            file_path: file_path.to_path_buf(),
+                            line_number,
+                            pattern_name: pattern.as_str().to_string(),
+                        });
+                    }
+                    true
+                }),
+            )
+            .context("failed to scan commit diff")?;
+        }
+        Ok(matches)
+    }
+}
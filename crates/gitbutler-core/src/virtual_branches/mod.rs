@@ -22,4 +22,7 @@ pub use remote::*;
 
 mod state;
 pub use state::VirtualBranches as VirtualBranchesState;
-pub use state::VirtualBranchesHandle;
+pub use state::{
+    OrderingViolation, VirtualBranchesBuilder, VirtualBranchesHandle, VirtualBranchesSnapshot,
+    CURRENT_SCHEMA_VERSION, VIRTUAL_BRANCHES_FILE_NAME,
+};
@@ -1,18 +1,30 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     path::{Path, PathBuf},
 };
 
-use crate::{error::Code, fs::read_toml_file_or_default};
-use anyhow::{anyhow, Result};
+use crate::{dedup::dedup, error::Code, fs::read_toml_file_or_default, git, project_repository};
+use anyhow::{anyhow, bail, Context, Result};
 use serde::{Deserialize, Serialize};
 
-use super::{target::Target, Branch};
+use super::{branch::OwnershipEntry, normalize_branch_name, target::Target, Branch};
 use crate::virtual_branches::BranchId;
 
+/// The name of the file the state of virtual branches is stored in, within the `GitButler`
+/// directory of a project.
+pub const VIRTUAL_BRANCHES_FILE_NAME: &str = "virtual_branches.toml";
+
+/// The version of the format of [`VirtualBranches`] written by this version of the app.
+pub const CURRENT_SCHEMA_VERSION: u32 = 1;
+
 /// The state of virtual branches data, as persisted in a TOML file.
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct VirtualBranches {
+    /// The version of the format the state was written in, `0` if it predates versioning.
+    ///
+    /// Older states are migrated to [`CURRENT_SCHEMA_VERSION`] when they are read.
+    #[serde(default)]
+    pub schema_version: u32,
     /// This is the target/base that is set when a repo is added to gb
     pub default_target: Option<Target>,
     /// The targets for each virtual branch
@@ -20,29 +32,127 @@ pub struct VirtualBranches {
     /// The current state of the virtual branches
     pub branches: HashMap<BranchId, Branch>,
 }
+
+impl VirtualBranches {
+    /// Starts building a state from scratch, e.g. to set up tests.
+    pub fn builder() -> VirtualBranchesBuilder {
+        VirtualBranchesBuilder {
+            state: VirtualBranches {
+                schema_version: CURRENT_SCHEMA_VERSION,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Parses a state stored as TOML, e.g. in a snapshot, migrating it to
+    /// [`CURRENT_SCHEMA_VERSION`] like states read from disk.
+    ///
+    /// Errors if `toml` is invalid or was written in a newer version of the format.
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        let mut state: VirtualBranches =
+            toml::from_str(toml).context("failed to parse virtual branches")?;
+        migrate(state.schema_version, &mut state)?;
+        Ok(state)
+    }
+
+    /// Iterates all virtual branches without cloning them.
+    pub fn iter_branches(&self) -> impl Iterator<Item = &Branch> {
+        self.branches.values()
+    }
+
+    /// Iterates all virtual branches that are applied to the workspace without cloning them.
+    pub fn iter_branches_in_workspace(&self) -> impl Iterator<Item = &Branch> {
+        self.iter_branches().filter(|branch| branch.applied)
+    }
+}
+
+/// Builds [`VirtualBranches`] one item at a time, see [`VirtualBranches::builder()`].
+#[derive(Debug)]
+pub struct VirtualBranchesBuilder {
+    state: VirtualBranches,
+}
+
+impl VirtualBranchesBuilder {
+    pub fn default_target(mut self, target: Target) -> Self {
+        self.state.default_target = Some(target);
+        self
+    }
+
+    pub fn branch_target(mut self, id: BranchId, target: Target) -> Self {
+        self.state.branch_targets.insert(id, target);
+        self
+    }
+
+    /// Adds `branch`, replacing any branch with the same ID.
+    pub fn branch(mut self, branch: Branch) -> Self {
+        self.state.branches.insert(branch.id, branch);
+        self
+    }
+
+    pub fn build(self) -> VirtualBranches {
+        self.state
+    }
+}
+
+/// A way in which the `order` of virtual branches differs from a contiguous, 0-based sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrderingViolation {
+    /// No branch has this order.
+    GapAt(usize),
+    /// More than one branch has this order.
+    Duplicate(usize, Vec<BranchId>),
+    /// The order of the branch is larger than the number of branches allows.
+    OutOfRange {
+        id: BranchId,
+        order: usize,
+        max: usize,
+    },
+}
+
 /// A handle to the state of virtual branches.
 ///
 /// For all operations, if the state file does not exist, it will be created.
+///
+/// Operations that modify the state hold an exclusive lock on `virtual_branches.lock` from
+/// reading the file until it is written back, so concurrent writers, even from other processes,
+/// can't lose each other's changes. Readers don't need the lock as the file is replaced atomically.
 pub struct VirtualBranchesHandle {
     /// The path to the file containing the virtual branches state.
     file_path: PathBuf,
+    /// The path to the file used to serialize writes to `file_path`.
+    lock_path: PathBuf,
 }
 
 impl VirtualBranchesHandle {
     /// Creates a new concurrency-safe handle to the state of virtual branches.
     pub fn new<P: AsRef<Path>>(base_path: P) -> Self {
-        let file_path = base_path.as_ref().join("virtual_branches.toml");
-        Self { file_path }
+        let file_path = base_path.as_ref().join(VIRTUAL_BRANCHES_FILE_NAME);
+        let lock_path = base_path.as_ref().join("virtual_branches.lock");
+        Self {
+            file_path,
+            lock_path,
+        }
+    }
+
+    /// Writes `state` to a new temporary directory and returns a handle to it, see
+    /// [`VirtualBranchesSnapshot`]. Useful to set up tests without writing fixtures.
+    ///
+    /// Errors if the temporary directory cannot be created or written.
+    pub fn from_snapshot(state: VirtualBranches) -> Result<VirtualBranchesSnapshot> {
+        let dir = tempfile::tempdir().context("failed to create snapshot directory")?;
+        let handle = Self::new(dir.path());
+        handle.write_file(&state)?;
+        Ok(VirtualBranchesSnapshot { handle, _dir: dir })
     }
 
     /// Persists the default target for the given repository.
     ///
     /// Errors if the file cannot be read or written.
     pub fn set_default_target(&self, target: Target) -> Result<()> {
-        let mut virtual_branches = self.read_file()?;
-        virtual_branches.default_target = Some(target);
-        self.write_file(&virtual_branches)?;
-        Ok(())
+        self.atomic_update(|virtual_branches| {
+            virtual_branches.default_target = Some(target);
+            Ok(())
+        })
     }
 
     /// Gets the default target for the given repository.
@@ -57,32 +167,132 @@ impl VirtualBranchesHandle {
 
     /// Sets the target for the given virtual branch.
     ///
-    /// Errors if the file cannot be read or written.
+    /// Errors if the branch doesn't exist or the file cannot be read or written.
     pub fn set_branch_target(&self, id: BranchId, target: Target) -> Result<()> {
-        let mut virtual_branches = self.read_file()?;
-        virtual_branches.branch_targets.insert(id, target);
-        self.write_file(&virtual_branches)?;
-        Ok(())
+        self.atomic_update(|virtual_branches| {
+            if !virtual_branches.branches.contains_key(&id) {
+                bail!("branch with ID {id} not found");
+            }
+            virtual_branches.branch_targets.insert(id, target);
+            Ok(())
+        })
+    }
+
+    /// Gets the target of the given virtual branch, or `None` if it has none of its own.
+    ///
+    /// Errors if the file cannot be read.
+    pub fn get_branch_target(&self, id: BranchId) -> Result<Option<Target>> {
+        let virtual_branches = self.read_file()?;
+        Ok(virtual_branches.branch_targets.get(&id).cloned())
     }
 
     /// Sets the state of the given virtual branch.
     ///
     /// Errors if the file cannot be read or written.
     pub fn set_branch(&self, branch: Branch) -> Result<()> {
-        let mut virtual_branches = self.read_file()?;
-        virtual_branches.branches.insert(branch.id, branch);
-        self.write_file(&virtual_branches)?;
-        Ok(())
+        self.atomic_update(|virtual_branches| {
+            virtual_branches.branches.insert(branch.id, branch);
+            Ok(())
+        })
     }
 
     /// Removes the given virtual branch.
     ///
     /// Errors if the file cannot be read or written.
     pub fn remove_branch(&self, id: BranchId) -> Result<()> {
-        let mut virtual_branches = self.read_file()?;
-        virtual_branches.branches.remove(&id);
-        self.write_file(&virtual_branches)?;
-        Ok(())
+        self.atomic_update(|virtual_branches| {
+            virtual_branches.branches.remove(&id);
+            Ok(())
+        })
+    }
+
+    /// Renames the given virtual branch to `new_name`, moving its reference in
+    /// `project_repository` along with it. If another virtual branch already has that name,
+    /// a number is appended to make it unique.
+    ///
+    /// The reference is moved while the state is locked and before it is written, so if moving
+    /// it fails, neither the state nor the references are changed.
+    ///
+    /// Errors if the branch doesn't exist, the file cannot be read or written or the reference
+    /// cannot be moved.
+    pub fn rename_branch(
+        &self,
+        project_repository: &project_repository::Repository,
+        id: BranchId,
+        new_name: String,
+    ) -> Result<()> {
+        self.atomic_update(|virtual_branches| {
+            let new_name = dedup(
+                &virtual_branches
+                    .iter_branches()
+                    .filter(|branch| branch.id != id)
+                    .map(|branch| branch.name.as_str())
+                    .collect::<Vec<_>>(),
+                &new_name,
+            );
+            let branch = virtual_branches
+                .branches
+                .get_mut(&id)
+                .ok_or_else(|| anyhow!("branch with ID {id} not found"))?;
+            let old_branch = branch.clone();
+            branch.name = new_name;
+            branch.updated_timestamp_ms = crate::time::now_ms();
+
+            if old_branch.refname() != branch.refname() {
+                project_repository.add_branch_reference(branch)?;
+                if let Err(err) = project_repository.delete_branch_reference(&old_branch) {
+                    project_repository.delete_branch_reference(branch)?;
+                    return Err(err);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Stores a copy of the given virtual branch under a new ID and the normalized form of
+    /// `new_name`, pointing to the same head and tree, and returns the new ID.
+    ///
+    /// The copy isn't applied to the workspace and owns no hunks, as those stay with the
+    /// original branch.
+    ///
+    /// Errors with [`Code::DuplicateBranchName`] if another virtual branch already has that name,
+    /// or if the branch doesn't exist or the file cannot be read or written.
+    pub fn clone_branch(&self, source_id: BranchId, new_name: String) -> Result<BranchId> {
+        let new_name = normalize_branch_name(&new_name);
+        let id = BranchId::generate();
+        self.atomic_update(|virtual_branches| {
+            if virtual_branches
+                .iter_branches()
+                .any(|branch| branch.name == new_name)
+            {
+                return Err(
+                    anyhow!("there already is a virtual branch named {new_name:?}")
+                        .context(Code::DuplicateBranchName),
+                );
+            }
+            let source = virtual_branches
+                .branches
+                .get(&source_id)
+                .ok_or_else(|| anyhow!("branch with ID {source_id} not found"))?;
+            let now = crate::time::now_ms();
+            let clone = Branch {
+                id,
+                name: new_name,
+                applied: false,
+                created_timestamp_ms: now,
+                updated_timestamp_ms: now,
+                ownership: Default::default(),
+                selected_for_changes: None,
+                ownership_log: Vec::new(),
+                ..source.clone()
+            };
+            if let Some(target) = virtual_branches.branch_targets.get(&source_id).cloned() {
+                virtual_branches.branch_targets.insert(id, target);
+            }
+            virtual_branches.branches.insert(id, clone);
+            Ok(())
+        })?;
+        Ok(id)
     }
 
     /// Gets the state of the given virtual branch.
@@ -109,6 +319,252 @@ impl VirtualBranchesHandle {
         Ok(branches)
     }
 
+    /// Counts all virtual branches.
+    ///
+    /// Errors if the file cannot be read.
+    pub fn branch_count(&self) -> Result<usize> {
+        let virtual_branches = self.read_file()?;
+        Ok(virtual_branches.branches.len())
+    }
+
+    /// Counts the virtual branches that are applied to the workspace.
+    ///
+    /// Errors if the file cannot be read.
+    pub fn workspace_branch_count(&self) -> Result<usize> {
+        let virtual_branches = self.read_file()?;
+        Ok(virtual_branches.iter_branches_in_workspace().count())
+    }
+
+    /// Lists all virtual branches whose upstream is on the remote named `remote_name`.
+    ///
+    /// Branches without an upstream are never included. Errors if the file cannot be read.
+    pub fn list_by_remote(&self, remote_name: &str) -> Result<Vec<Branch>> {
+        let virtual_branches = self.read_file()?;
+        Ok(virtual_branches
+            .branches
+            .into_values()
+            .filter(|branch| {
+                branch
+                    .upstream
+                    .as_ref()
+                    .map_or(false, |upstream| upstream.remote() == remote_name)
+            })
+            .collect())
+    }
+
+    /// Renders the upstream of every applied virtual branch that has one as `[branch "name"]`
+    /// sections, ready to be pasted into `.git/config`, in the order of the branches.
+    ///
+    /// Errors if the file cannot be read.
+    pub fn export_gitconfig_snippet(&self) -> Result<String> {
+        let virtual_branches = self.read_file()?;
+        let mut tracking = virtual_branches
+            .iter_branches_in_workspace()
+            .filter_map(|branch| Some((branch, branch.upstream.as_ref()?)))
+            .collect::<Vec<_>>();
+        tracking.sort_by_key(|(branch, _)| (branch.order, branch.id));
+
+        let mut snippet = String::new();
+        for (branch, upstream) in tracking {
+            snippet.push_str(&format!(
+                "[branch \"{}\"]\n\tremote = {}\n\tmerge = refs/heads/{}\n",
+                git::VirtualRefname::from(branch).branch(),
+                upstream.remote(),
+                upstream.branch()
+            ));
+        }
+        Ok(snippet)
+    }
+
+    /// Gets the virtual branch with the given `name`, or `None` if there is no such branch.
+    ///
+    /// Errors if the file cannot be read, or if more than one branch has that name.
+    pub fn find_by_name(&self, name: &str) -> Result<Option<Branch>> {
+        let virtual_branches = self.read_file()?;
+        find_unique_by_name(virtual_branches.branches.into_values(), name)
+    }
+
+    /// Like [`Self::find_by_name()`], but only considers branches that are applied to the workspace.
+    pub fn find_by_name_in_workspace(&self, name: &str) -> Result<Option<Branch>> {
+        let virtual_branches = self.read_file()?;
+        find_unique_by_name(
+            virtual_branches
+                .branches
+                .into_values()
+                .filter(|branch| branch.applied),
+            name,
+        )
+    }
+
+    /// Replaces the whole state with `state`, e.g. when restoring a snapshot.
+    ///
+    /// The current state isn't read, so this also works if it can't be parsed anymore.
+    ///
+    /// Errors if the file cannot be written.
+    pub fn replace_state(&self, state: &VirtualBranches) -> Result<()> {
+        let _lock = self.lock()?;
+        self.write_file(state)
+    }
+
+    /// Serializes the whole state to pretty-printed JSON, e.g. to back it up or move it to
+    /// another machine.
+    ///
+    /// Errors if the file cannot be read.
+    pub fn export_to_json(&self) -> Result<String> {
+        let virtual_branches = self.read_file()?;
+        serde_json::to_string_pretty(&virtual_branches)
+            .context("failed to serialize virtual branches")
+    }
+
+    /// Merges state previously produced by [`Self::export_to_json()`] into the current state.
+    ///
+    /// Branches and branch targets are replaced by ID, and the default target is replaced
+    /// if `json` has one. Branches that are not part of `json` are left untouched.
+    ///
+    /// Errors if `json` is invalid or the file cannot be read or written.
+    pub fn import_from_json(&self, json: &str) -> Result<()> {
+        let mut imported: VirtualBranches =
+            serde_json::from_str(json).context("failed to parse virtual branches")?;
+        migrate(imported.schema_version, &mut imported)?;
+        self.atomic_update(|virtual_branches| {
+            if imported.default_target.is_some() {
+                virtual_branches.default_target = imported.default_target;
+            }
+            virtual_branches
+                .branch_targets
+                .extend(imported.branch_targets);
+            virtual_branches.branches.extend(imported.branches);
+            Ok(())
+        })
+    }
+
+    /// Merges the branches of `other` into this state, e.g. when the state is shared between
+    /// machines. Of two branches with the same ID, the one updated last is kept, along with
+    /// its branch target.
+    ///
+    /// Returns the IDs of the branches that were taken from `other`.
+    /// Errors if either file cannot be read, or if this file cannot be written.
+    pub fn merge_from_handle(&self, other: &VirtualBranchesHandle) -> Result<Vec<BranchId>> {
+        let theirs = other.read_file()?;
+        let mut taken = Vec::new();
+        self.atomic_update(|virtual_branches| {
+            for (id, branch) in theirs.branches {
+                let is_newer = virtual_branches.branches.get(&id).map_or(true, |ours| {
+                    branch.updated_timestamp_ms > ours.updated_timestamp_ms
+                });
+                if !is_newer {
+                    continue;
+                }
+                match theirs.branch_targets.get(&id) {
+                    Some(target) => {
+                        virtual_branches.branch_targets.insert(id, target.clone());
+                    }
+                    None => {
+                        virtual_branches.branch_targets.remove(&id);
+                    }
+                }
+                virtual_branches.branches.insert(id, branch);
+                taken.push(id);
+            }
+            Ok(())
+        })?;
+        taken.sort();
+        Ok(taken)
+    }
+
+    /// Removes the targets of branches that don't exist anymore and returns how many were removed.
+    ///
+    /// Errors if the file cannot be read or written.
+    pub fn compact_branch_targets(&self) -> Result<usize> {
+        let mut removed = 0;
+        self.atomic_update(|virtual_branches| {
+            let before = virtual_branches.branch_targets.len();
+            let branches = &virtual_branches.branches;
+            virtual_branches
+                .branch_targets
+                .retain(|id, _| branches.contains_key(id));
+            removed = before - virtual_branches.branch_targets.len();
+            Ok(())
+        })?;
+        Ok(removed)
+    }
+
+    /// Checks that the `order` of all virtual branches, applied or not, forms a contiguous,
+    /// 0-based sequence like the one [`Self::reorder_branches()`] produces.
+    ///
+    /// Returns an empty list if that is the case.
+    pub fn validate_ordering(&self) -> Result<Vec<OrderingViolation>> {
+        let virtual_branches = self.read_file()?;
+        Ok(ordering_violations(&virtual_branches))
+    }
+
+    /// Renumbers all virtual branches so their `order` forms a contiguous, 0-based sequence,
+    /// keeping their relative order. Ties are broken by creation time.
+    ///
+    /// Errors if the file cannot be read or written.
+    pub fn repair_ordering(&self) -> Result<()> {
+        self.atomic_update(|virtual_branches| {
+            if ordering_violations(virtual_branches).is_empty() {
+                return Ok(());
+            }
+            let mut branches = virtual_branches.branches.values_mut().collect::<Vec<_>>();
+            branches.sort_by_key(|branch| (branch.order, branch.created_timestamp_ms, branch.id));
+            for (order, branch) in branches.into_iter().enumerate() {
+                branch.order = order;
+            }
+            Ok(())
+        })
+    }
+
+    /// Reads the state, passes it to `f` for modification and writes it back, holding the
+    /// write lock throughout so no concurrent modification, including
+    /// [`Self::replace_state()`], can be lost in between.
+    ///
+    /// Nothing is written if `f` fails. Errors if the file cannot be read or written.
+    pub fn atomic_update<F>(&self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut VirtualBranches) -> Result<()>,
+    {
+        let _lock = self.lock()?;
+        let mut virtual_branches = self.read_file()?;
+        f(&mut virtual_branches)?;
+        self.write_file(&virtual_branches)
+    }
+
+    /// Sets the `order` of the branches with `ordered_ids` to their position in that list.
+    /// All other branches are placed after them, keeping their relative order.
+    ///
+    /// Errors if an ID is listed twice or doesn't belong to a branch, in which case nothing
+    /// is changed, or if the file cannot be read or written.
+    pub fn reorder_branches(&self, ordered_ids: &[BranchId]) -> Result<()> {
+        self.atomic_update(|virtual_branches| {
+            for (index, id) in ordered_ids.iter().enumerate() {
+                if ordered_ids[..index].contains(id) {
+                    bail!("branch with ID {id} is listed more than once");
+                }
+                if !virtual_branches.branches.contains_key(id) {
+                    bail!("branch with ID {id} not found");
+                }
+            }
+
+            let mut unlisted = virtual_branches
+                .branches
+                .values_mut()
+                .filter(|branch| !ordered_ids.contains(&branch.id))
+                .collect::<Vec<_>>();
+            unlisted.sort_by_key(|branch| (branch.order, branch.created_timestamp_ms, branch.id));
+            for (offset, branch) in unlisted.into_iter().enumerate() {
+                branch.order = ordered_ids.len() + offset;
+            }
+            for (order, id) in ordered_ids.iter().enumerate() {
+                if let Some(branch) = virtual_branches.branches.get_mut(id) {
+                    branch.order = order;
+                }
+            }
+            Ok(())
+        })
+    }
+
     /// Checks if the state file exists.
     ///
     /// This would only be false if the application just updated from a very old verion.
@@ -120,7 +576,16 @@ impl VirtualBranchesHandle {
     ///
     /// If the file does not exist, it will be created.
     fn read_file(&self) -> Result<VirtualBranches> {
-        read_toml_file_or_default(&self.file_path)
+        read(&self.file_path)
+    }
+
+    /// Acquires the exclusive write lock, which is released when the returned value is dropped.
+    fn lock(&self) -> Result<fslock::LockFile> {
+        let mut lock = fslock::LockFile::open(&self.lock_path)
+            .with_context(|| format!("failed to open {}", self.lock_path.display()))?;
+        lock.lock()
+            .with_context(|| format!("failed to lock {}", self.lock_path.display()))?;
+        Ok(lock)
     }
 
     fn write_file(&self, virtual_branches: &VirtualBranches) -> Result<()> {
@@ -128,6 +593,113 @@ impl VirtualBranchesHandle {
     }
 }
 
+/// A handle to a private copy of the state of virtual branches, created with
+/// [`VirtualBranchesHandle::from_snapshot()`].
+///
+/// The copy lives in a temporary directory that is deleted when this value is dropped, so it
+/// must outlive all uses of the handle it dereferences to.
+pub struct VirtualBranchesSnapshot {
+    handle: VirtualBranchesHandle,
+    _dir: tempfile::TempDir,
+}
+
+impl std::ops::Deref for VirtualBranchesSnapshot {
+    type Target = VirtualBranchesHandle;
+
+    fn deref(&self) -> &Self::Target {
+        &self.handle
+    }
+}
+
+fn read<P: AsRef<Path>>(file_path: P) -> Result<VirtualBranches> {
+    let mut virtual_branches: VirtualBranches = read_toml_file_or_default(file_path.as_ref())?;
+    migrate(virtual_branches.schema_version, &mut virtual_branches)?;
+    Ok(virtual_branches)
+}
+
 fn write<P: AsRef<Path>>(file_path: P, virtual_branches: &VirtualBranches) -> Result<()> {
     crate::fs::write(file_path, toml::to_string(&virtual_branches)?)
 }
+
+/// Brings `state`, written in version `from` of the format, up to [`CURRENT_SCHEMA_VERSION`].
+///
+/// Errors if `state` was written by a newer version of the app, as it could contain data that
+/// would be lost when writing it back.
+fn migrate(from: u32, state: &mut VirtualBranches) -> Result<()> {
+    if from > CURRENT_SCHEMA_VERSION {
+        bail!(
+            "virtual branches were written in version {from} of the format, but only versions up to {CURRENT_SCHEMA_VERSION} are supported"
+        );
+    }
+    for version in from..CURRENT_SCHEMA_VERSION {
+        match version {
+            0 => migrate_v0_to_v1(state),
+            _ => bail!("don't know how to migrate virtual branches from version {version}"),
+        }
+        state.schema_version = version + 1;
+    }
+    Ok(())
+}
+
+/// Version 1 introduced the ownership log. Files that are owned already get an entry starting at
+/// the last update of their branch, as that is the latest they could have been assigned.
+fn migrate_v0_to_v1(state: &mut VirtualBranches) {
+    for branch in state.branches.values_mut() {
+        for claim in &branch.ownership.claims {
+            let is_logged = branch.ownership_log.iter().any(|entry| {
+                entry.file_path == claim.file_path && entry.unassigned_at_ms.is_none()
+            });
+            if !is_logged {
+                branch.ownership_log.push(OwnershipEntry {
+                    file_path: claim.file_path.clone(),
+                    stack_id: branch.id,
+                    assigned_at_ms: branch.updated_timestamp_ms,
+                    unassigned_at_ms: None,
+                });
+            }
+        }
+    }
+}
+
+fn find_unique_by_name(
+    branches: impl Iterator<Item = Branch>,
+    name: &str,
+) -> Result<Option<Branch>> {
+    let mut matching = branches.filter(|branch| branch.name == name);
+    let found = matching.next();
+    if matching.next().is_some() {
+        bail!("there is more than one virtual branch named {name:?}");
+    }
+    Ok(found)
+}
+
+fn ordering_violations(virtual_branches: &VirtualBranches) -> Vec<OrderingViolation> {
+    let branches = virtual_branches.iter_branches().collect::<Vec<_>>();
+
+    let mut by_order: BTreeMap<usize, Vec<BranchId>> = BTreeMap::new();
+    for branch in &branches {
+        by_order.entry(branch.order).or_default().push(branch.id);
+    }
+
+    let mut violations = Vec::new();
+    for order in 0..branches.len() {
+        match by_order.get_mut(&order) {
+            None => violations.push(OrderingViolation::GapAt(order)),
+            Some(ids) if ids.len() > 1 => {
+                ids.sort();
+                violations.push(OrderingViolation::Duplicate(order, ids.clone()));
+            }
+            Some(_) => {}
+        }
+    }
+    for (order, ids) in by_order.range(branches.len()..) {
+        for id in ids {
+            violations.push(OrderingViolation::OutOfRange {
+                id: *id,
+                order: *order,
+                max: branches.len() - 1,
+            });
+        }
+    }
+    violations
+}
@@ -207,11 +207,7 @@ impl From<git2::Signature<'_>> for Author {
         let name = value.name().unwrap_or_default().to_string();
         let email = value.email().unwrap_or_default().to_string();
 
-        let gravatar_url = url::Url::parse(&format!(
-            "https://www.gravatar.com/avatar/{:x}?s=100&r=g&d=retro",
-            md5::compute(email.to_lowercase())
-        ))
-        .unwrap();
+        let gravatar_url = url::Url::parse(&gravatar_url_for_email(&email, 100)).unwrap();
 
         Author {
             name,
@@ -221,6 +217,31 @@ impl From<git2::Signature<'_>> for Author {
     }
 }
 
+impl Author {
+    /// Returns the URL of the Gravatar of this author, `size` pixels wide,
+    /// or `None` if the author has no email.
+    pub fn gravatar_url(&self, size: u32) -> Option<String> {
+        (!self.email.trim().is_empty()).then(|| gravatar_url_for_email(&self.email, size))
+    }
+
+    /// Like the [`From`] implementation, but maps the identity in `signature` to the canonical
+    /// one listed in `mailmap`, so the same person is recognized across names and emails.
+    pub fn from_with_mailmap(signature: git2::Signature, mailmap: &git2::Mailmap) -> Self {
+        match mailmap.resolve_signature(&signature) {
+            Ok(resolved) => resolved.into(),
+            Err(_) => signature.into(),
+        }
+    }
+}
+
+/// Gravatar hashes the trimmed, lowercase form of an email.
+fn gravatar_url_for_email(email: &str, size: u32) -> String {
+    format!(
+        "https://www.gravatar.com/avatar/{:x}?s={size}&r=g&d=retro",
+        md5::compute(email.trim().to_lowercase())
+    )
+}
+
 pub fn normalize_branch_name(name: &str) -> String {
     let pattern = Regex::new("[^A-Za-z0-9_/.#]+").unwrap();
     pattern.replace_all(name, "-").to_string()
@@ -636,6 +657,9 @@ pub fn unapply_branch(
                     .context("failed to ensure selected for changes")?;
 
                 project_repository.delete_branch_reference(&target_branch)?;
+                target_branch
+                    .discard_checkpoints(repo)
+                    .context("failed to delete checkpoint references")?;
                 return Ok(None);
             }
 
@@ -689,25 +713,6 @@ pub fn unapply_branch(
     Ok(Some(target_branch))
 }
 
-fn find_base_tree<'a>(
-    repo: &'a git2::Repository,
-    branch_commit: &'a git2::Commit<'a>,
-    target_commit: &'a git2::Commit<'a>,
-) -> Result<git2::Tree<'a>> {
-    // find merge base between target_commit and branch_commit
-    let merge_base = repo
-        .merge_base(target_commit.id(), branch_commit.id())
-        .context("failed to find merge base")?;
-    // turn oid into a commit
-    let merge_base_commit = repo
-        .find_commit(merge_base)
-        .context("failed to find merge base commit")?;
-    let base_tree = merge_base_commit
-        .tree()
-        .context("failed to get base tree object")?;
-    Ok(base_tree)
-}
-
 pub fn list_virtual_branches(
     project_repository: &project_repository::Repository,
 ) -> Result<(Vec<VirtualBranch>, Vec<diff::FileDiff>)> {
@@ -1063,6 +1068,9 @@ pub fn create_virtual_branch(
         order,
         selected_for_changes,
         allow_rebasing: project_repository.project().ok_with_force_push.into(),
+        checkpoints: vec![],
+        ownership_log: vec![],
+        source_refname: None,
     };
 
     if let Some(ownership) = &create.ownership {
@@ -1312,21 +1320,8 @@ pub fn update_branch(
     }
 
     if let Some(name) = &branch_update.name {
-        let all_virtual_branches = vb_state
-            .list_branches()
-            .context("failed to read virtual branches")?;
-
-        project_repository.delete_branch_reference(&branch)?;
-
-        branch.name = dedup(
-            &all_virtual_branches
-                .iter()
-                .map(|b| b.name.as_str())
-                .collect::<Vec<_>>(),
-            name,
-        );
-
-        project_repository.add_branch_reference(&branch)?;
+        vb_state.rename_branch(project_repository, branch.id, name.clone())?;
+        branch.name = vb_state.get_branch(branch.id)?.name;
     };
 
     if let Some(updated_upstream) = &branch_update.upstream {
@@ -1384,7 +1379,7 @@ pub fn delete_branch(
     branch_id: BranchId,
 ) -> Result<()> {
     let vb_state = project_repository.project().virtual_branches();
-    let Some(branch) = vb_state.try_branch(branch_id)? else {
+    let Some(mut branch) = vb_state.try_branch(branch_id)? else {
         return Ok(());
     };
     _ = project_repository
@@ -1400,6 +1395,9 @@ pub fn delete_branch(
         .context("Failed to remove branch")?;
 
     project_repository.delete_branch_reference(&branch)?;
+    branch
+        .discard_checkpoints(project_repository.repo())
+        .context("failed to delete checkpoint references")?;
 
     ensure_selected_for_changes(&vb_state).context("failed to ensure selected for changes")?;
 
@@ -1460,7 +1458,7 @@ fn set_ownership(
 
     // Updates the claiming branch that was passed as mutable state with the new ownership claims
     // TODO: remove mutable reference to target_branch
-    target_branch.ownership = ownership.clone();
+    target_branch.replace_ownership(ownership.clone());
 
     Ok(())
 }
@@ -1569,6 +1567,27 @@ pub fn get_status_by_branch(
     ))
 }
 
+/// Returns the ID of the applied branch that currently owns uncommitted changes to `path`, if any.
+pub fn get_current_file_owner(
+    project_repository: &project_repository::Repository,
+    path: &Path,
+) -> Result<Option<BranchId>> {
+    let vb_state = project_repository.project().virtual_branches();
+    Ok(vb_state
+        .list_branches()
+        .context("failed to read virtual branches")?
+        .into_iter()
+        .filter(|branch| branch.applied)
+        .find(|branch| {
+            branch
+                .ownership
+                .claims
+                .iter()
+                .any(|claim| claim.file_path == path)
+        })
+        .map(|branch| branch.id))
+}
+
 // given a list of non applied virtual branches, return the status of each file, comparing the default target with
 // virtual branch latest tree
 //
@@ -1865,7 +1884,7 @@ fn get_applied_status(
             })
             .collect();
 
-        branch.ownership = BranchOwnershipClaims { claims: new_claims };
+        branch.replace_ownership(BranchOwnershipClaims { claims: new_claims });
     }
 
     let max_selected_for_changes = virtual_branches
@@ -1904,7 +1923,7 @@ fn get_applied_status(
                 _ => vec![],
             };
 
-            virtual_branches[vbranch_pos].ownership.put(OwnershipClaim {
+            virtual_branches[vbranch_pos].assign_hunk(OwnershipClaim {
                 file_path: filepath.clone(),
                 hunks: vec![Hunk::from(&hunk).with_hash(Hunk::hash_diff(&hunk.diff_lines))],
             });
@@ -1978,6 +1997,51 @@ fn virtual_hunks_into_virtual_files(
 }
 
 // reset virtual branch to a specific commit
+/// Restores the head and uncommitted changes of the virtual branch `branch_id` to those
+/// recorded in its checkpoint `checkpoint_id`.
+///
+/// If the branch is applied, its uncommitted changes in the worktree are replaced by the ones
+/// of the checkpoint and the integration commit is updated. Errors with
+/// [`Marker::ProjectConflict`] if that conflicts with the worktree, leaving everything as is.
+pub fn restore_branch_checkpoint(
+    project_repository: &project_repository::Repository,
+    branch_id: BranchId,
+    checkpoint_id: branch::CheckpointId,
+) -> Result<()> {
+    let vb_state = project_repository.project().virtual_branches();
+    let mut branch = vb_state.get_branch(branch_id)?;
+    let repo = project_repository.repo();
+
+    let old_tree = repo.find_tree(branch.tree)?;
+    branch.restore_checkpoint(repo, checkpoint_id)?;
+
+    if branch.applied {
+        let checkpoint_tree = repo.find_tree(branch.tree)?;
+        let wd_tree = repo.get_wd_tree()?;
+        let mut merge_index = repo.merge_trees(&old_tree, &wd_tree, &checkpoint_tree, None)?;
+        if merge_index.has_conflicts() {
+            return Err(anyhow!(
+                "checkpoint of branch {} conflicts with the workspace",
+                branch.name
+            ))
+            .context(Marker::ProjectConflict);
+        }
+        repo.checkout_index_builder(&mut merge_index)
+            .force()
+            .checkout()?;
+    }
+
+    let applied = branch.applied;
+    vb_state
+        .set_branch(branch)
+        .context("failed to write branch")?;
+    if applied {
+        super::integration::update_gitbutler_integration(&vb_state, project_repository)
+            .context("failed to update gitbutler integration")?;
+    }
+    Ok(())
+}
+
 pub fn reset_branch(
     project_repository: &project_repository::Repository,
     branch_id: BranchId,
@@ -2027,7 +2091,7 @@ pub fn reset_branch(
     for (path, filediff) in diff {
         for hunk in filediff.hunks {
             let hash = Hunk::hash_diff(&hunk.diff_lines);
-            branch.ownership.put(
+            branch.assign_hunk(
                 format!(
                     "{}:{}-{}-{:?}",
                     path.display(),
@@ -2514,7 +2578,11 @@ pub fn is_remote_branch_mergeable(
         .find_commit(branch_oid)
         .context("failed to find branch commit")?;
 
-    let base_tree = find_base_tree(project_repository.repo(), &branch_commit, &target_commit)?;
+    let base_tree = branch::merge_base_tree(
+        project_repository.repo(),
+        target_commit.id(),
+        branch_commit.id(),
+    )?;
 
     let wd_tree = project_repository.repo().get_wd_tree()?;
 
@@ -2549,17 +2617,7 @@ pub fn is_virtual_branch_mergeable(
         return Ok(false);
     }
 
-    let branch_commit = project_repository
-        .repo()
-        .find_commit(branch.head)
-        .context("failed to find branch commit")?;
-
-    let target_commit = project_repository
-        .repo()
-        .find_commit(default_target.sha)
-        .context("failed to find target commit")?;
-
-    let base_tree = find_base_tree(project_repository.repo(), &branch_commit, &target_commit)?;
+    let base_tree = branch.get_base_tree(project_repository.repo(), &default_target)?;
 
     let wd_tree = project_repository.repo().get_wd_tree()?;
 
@@ -3632,7 +3690,7 @@ pub fn move_commit(
             )
         })
         .map(|(file_path, hunks)| OwnershipClaim { file_path, hunks })
-        .flat_map(|file_ownership| source_branch.ownership.take(&file_ownership))
+        .flat_map(|file_ownership| source_branch.unassign_hunk(&file_ownership))
         .collect::<Vec<_>>();
 
     // reset the source branch to the parent commit
@@ -3646,7 +3704,7 @@ pub fn move_commit(
         let mut destination_branch = vb_state.get_branch(target_branch_id)?;
 
         for ownership in ownerships_to_transfer {
-            destination_branch.ownership.put(ownership);
+            destination_branch.assign_hunk(ownership);
         }
 
         let new_destination_tree_oid = write_tree_onto_commit(
@@ -3793,6 +3851,9 @@ pub fn create_virtual_branch_from_branch(
         order,
         selected_for_changes,
         allow_rebasing: project_repository.project().ok_with_force_push.into(),
+        checkpoints: vec![],
+        ownership_log: vec![],
+        source_refname: None,
     };
 
     vb_state.set_branch(branch.clone())?;
@@ -3812,6 +3873,107 @@ pub fn create_virtual_branch_from_branch(
     }
 }
 
+/// Brings the commits of the local branch `local_branch_name` into the virtual branch `branch_id`.
+///
+/// If the virtual branch has no commits of its own beyond the merge base of the local branch,
+/// its head is simply moved to the head of the local branch. Otherwise the commits of the local
+/// branch since that merge base are cherry-picked on top of the virtual branch. Syncing a branch
+/// that already contains the local commits leaves its commits untouched.
+///
+/// The local branch is recorded as [`branch::Branch::source_refname`].
+pub fn sync_branch_from_local_branch(
+    project_repository: &project_repository::Repository,
+    branch_id: BranchId,
+    local_branch_name: &str,
+) -> Result<branch::Branch> {
+    let vb_state = project_repository.project().virtual_branches();
+    let mut branch = vb_state.get_branch(branch_id)?;
+    let repo = project_repository.repo();
+
+    let local_head = repo
+        .find_branch(local_branch_name, git2::BranchType::Local)
+        .context(format!("failed to find local branch {local_branch_name}"))?
+        .get()
+        .peel_to_commit()
+        .context("failed to peel to commit")?
+        .id();
+    let source_refname = git::LocalRefname::new(local_branch_name, None);
+    if local_head == branch.head || repo.graph_descendant_of(branch.head, local_head)? {
+        return record_sync_source(&vb_state, branch, source_refname);
+    }
+    let merge_base = repo
+        .merge_base(branch.head, local_head)
+        .context("failed to find merge base")?;
+
+    let new_head = if merge_base == branch.head {
+        local_head
+    } else {
+        cherry_rebase(project_repository, branch.head, merge_base, local_head)?
+            .unwrap_or(branch.head)
+    };
+    let new_head_tree = repo.find_commit(new_head)?.tree()?;
+    if new_head == branch.head || new_head_tree.id() == repo.find_commit(branch.head)?.tree_id() {
+        // the local commits were picked before, only empty commits would be added
+        return record_sync_source(&vb_state, branch, source_refname);
+    }
+    branch.source_refname = Some(source_refname);
+
+    if branch.applied {
+        let wd_tree = repo.get_wd_tree()?;
+        let integration_tree = repo
+            .find_commit(get_workspace_head(&vb_state, project_repository)?)?
+            .tree()?;
+        let mut merge_index =
+            repo.merge_trees(&integration_tree, &new_head_tree, &wd_tree, None)?;
+        if merge_index.has_conflicts() {
+            return Err(anyhow!(
+                "local branch {local_branch_name} conflicts with the workspace"
+            ))
+            .context(Marker::ProjectConflict);
+        }
+        repo.checkout_index_builder(&mut merge_index)
+            .force()
+            .checkout()?;
+        branch.head = new_head;
+        branch.tree = new_head_tree.id();
+        branch.updated_timestamp_ms = crate::time::now_ms();
+        vb_state.set_branch(branch.clone())?;
+        super::integration::update_gitbutler_integration(&vb_state, project_repository)?;
+    } else {
+        let old_head_tree = repo.find_commit(branch.head)?.tree()?;
+        let branch_tree = repo.find_tree(branch.tree)?;
+        let mut merge_index =
+            repo.merge_trees(&old_head_tree, &new_head_tree, &branch_tree, None)?;
+        if merge_index.has_conflicts() {
+            return Err(anyhow!(
+                "local branch {local_branch_name} conflicts with branch {}",
+                branch.name
+            ))
+            .context(Marker::BranchConflict);
+        }
+        branch.head = new_head;
+        branch.tree = merge_index.write_tree_to(repo)?;
+        branch.updated_timestamp_ms = crate::time::now_ms();
+        vb_state.set_branch(branch.clone())?;
+    }
+    project_repository.add_branch_reference(&branch)?;
+
+    Ok(branch)
+}
+
+/// Stores `source_refname` as the local branch `branch` was synced from, unless it already is.
+fn record_sync_source(
+    vb_state: &VirtualBranchesHandle,
+    mut branch: branch::Branch,
+    source_refname: git::LocalRefname,
+) -> Result<branch::Branch> {
+    if branch.source_refname.as_ref() != Some(&source_refname) {
+        branch.source_refname = Some(source_refname);
+        vb_state.set_branch(branch.clone())?;
+    }
+    Ok(branch)
+}
+
 /// Just like [`diffy::apply()`], but on error it will attach hashes of the input `base_image` and `patch`.
 pub fn apply<S: AsRef<[u8]>>(base_image: S, patch: &Patch<'_, [u8]>) -> Result<BString> {
     fn md5_hash_hex(b: impl AsRef<[u8]>) -> String {
@@ -3930,4 +4092,73 @@ mod tests {
         assert_eq!(normalize_branch_name("foo#branch"), "foo#branch");
         assert_eq!(normalize_branch_name("foo!branch"), "foo-branch");
     }
+
+    #[test]
+    fn author_gravatar_url() {
+        let author = |email: &str| Author {
+            name: "name".to_string(),
+            email: email.to_string(),
+            gravatar_url: url::Url::parse("https://example.com").unwrap(),
+        };
+        let expected = |size: u32| {
+            format!(
+                "https://www.gravatar.com/avatar/{:x}?s={size}&r=g&d=retro",
+                md5::compute("jane@example.com")
+            )
+        };
+
+        assert_eq!(author("").gravatar_url(80), None);
+        assert_eq!(author("  ").gravatar_url(80), None);
+        assert_eq!(
+            author("jane@example.com").gravatar_url(80),
+            Some(expected(80))
+        );
+        assert_eq!(
+            author(" jane@example.com ").gravatar_url(80),
+            Some(expected(80))
+        );
+        assert_eq!(
+            author("Jane@Example.COM").gravatar_url(80),
+            Some(expected(80))
+        );
+
+        let from_signature =
+            Author::from(git2::Signature::now("name", "Jane@Example.COM").unwrap());
+        assert_eq!(from_signature.gravatar_url.as_str(), expected(100));
+        assert_eq!(
+            from_signature.gravatar_url(100).as_deref(),
+            Some(from_signature.gravatar_url.as_str()),
+            "the field and the method agree"
+        );
+    }
+
+    #[test]
+    fn author_from_with_mailmap() {
+        let mut mailmap = git2::Mailmap::new().unwrap();
+        mailmap
+            .add_entry(
+                Some("Jane Doe"),
+                Some("jane@example.com"),
+                None,
+                "jane@old-job.example.com",
+            )
+            .unwrap();
+
+        let old = git2::Signature::now("jane", "jane@old-job.example.com").unwrap();
+        let current = git2::Signature::now("Jane Doe", "jane@example.com").unwrap();
+        assert_eq!(
+            Author::from_with_mailmap(old, &mailmap),
+            Author::from_with_mailmap(current.clone(), &mailmap)
+        );
+        assert_eq!(
+            Author::from_with_mailmap(current.clone(), &mailmap),
+            current.into()
+        );
+
+        let unmapped = git2::Signature::now("Someone", "someone@example.com").unwrap();
+        assert_eq!(
+            Author::from_with_mailmap(unmapped.clone(), &mailmap),
+            unmapped.into()
+        );
+    }
 }
@@ -0,0 +1,164 @@
+use std::path::Path;
+
+use anyhow::Result;
+use gitbutler_core::{
+    project_repository::LogUntil,
+    virtual_branches::{branch::BranchCreateRequest, commit, create_virtual_branch},
+};
+use gitbutler_testsupport::{virtual_branches::set_test_target, Case, Suite};
+
+const TEST_COMMAND: &str = "grep -q ok status";
+
+/// Never exits on its own, so it only ends by being killed.
+#[cfg(unix)]
+const BLOCKING_COMMAND: &str = "tail -f /dev/null";
+
+/// Creates a branch with `count` commits, where commits from `broken_from` onwards fail the test.
+fn branch_with_regression(case: &Case, count: usize, broken_from: usize) -> Result<Vec<git2::Oid>> {
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = case;
+    set_test_target(project_repository)?;
+    let branch_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+
+    for i in 0..count {
+        let status = if i < broken_from { "ok" } else { "broken" };
+        std::fs::write(
+            Path::new(&project.path).join("status"),
+            format!("{status} {i}\n"),
+        )?;
+        commit(
+            project_repository,
+            branch_id,
+            &format!("commit {i}"),
+            None,
+            None,
+            false,
+        )?;
+    }
+
+    let vb_state = project.virtual_branches();
+    let branch = vb_state.get_branch(branch_id)?;
+    let target = vb_state.get_default_target()?;
+    let mut commits = project_repository.l(branch.head, LogUntil::Commit(target.sha))?;
+    commits.reverse();
+    Ok(commits)
+}
+
+#[cfg(unix)]
+#[test]
+fn finds_first_failing_commit() -> Result<()> {
+    let suite = Suite::default();
+    let case = suite.new_case();
+    let commits = branch_with_regression(&case, 7, 4)?;
+
+    let vb_state = case.project.virtual_branches();
+    let branch = vb_state.list_branches()?.pop().unwrap();
+    let target = vb_state.get_default_target()?;
+
+    let regression =
+        branch.find_regression_commit(case.project_repository.repo(), &target, TEST_COMMAND, 30)?;
+    assert_eq!(regression, Some(commits[4]));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn first_commit_is_the_regression() -> Result<()> {
+    let suite = Suite::default();
+    let case = suite.new_case();
+    let commits = branch_with_regression(&case, 3, 0)?;
+
+    let vb_state = case.project.virtual_branches();
+    let branch = vb_state.list_branches()?.pop().unwrap();
+    let target = vb_state.get_default_target()?;
+
+    let regression =
+        branch.find_regression_commit(case.project_repository.repo(), &target, TEST_COMMAND, 30)?;
+    assert_eq!(regression, Some(commits[0]));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn passing_head_has_no_regression() -> Result<()> {
+    let suite = Suite::default();
+    let case = suite.new_case();
+    branch_with_regression(&case, 3, 3)?;
+
+    let vb_state = case.project.virtual_branches();
+    let branch = vb_state.list_branches()?.pop().unwrap();
+    let target = vb_state.get_default_target()?;
+
+    let regression =
+        branch.find_regression_commit(case.project_repository.repo(), &target, TEST_COMMAND, 30)?;
+    assert_eq!(regression, None);
+    assert_eq!(
+        std::fs::read_to_string(case.project.path.join("status"))?,
+        "ok 2\n",
+        "the workspace is left untouched"
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn timeout_counts_as_failure() -> Result<()> {
+    let suite = Suite::default();
+    let case = suite.new_case();
+    let commits = branch_with_regression(&case, 2, 2)?;
+
+    let vb_state = case.project.virtual_branches();
+    let branch = vb_state.list_branches()?.pop().unwrap();
+    let target = vb_state.get_default_target()?;
+
+    let regression = branch.find_regression_commit(
+        case.project_repository.repo(),
+        &target,
+        BLOCKING_COMMAND,
+        0,
+    )?;
+    assert_eq!(regression, Some(commits[0]));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn timeout_kills_processes_started_by_the_command() -> Result<()> {
+    let suite = Suite::default();
+    let case = suite.new_case();
+    branch_with_regression(&case, 1, 1)?;
+
+    let vb_state = case.project.virtual_branches();
+    let branch = vb_state.list_branches()?.pop().unwrap();
+    let target = vb_state.get_default_target()?;
+
+    let pid_dir = gitbutler_testsupport::temp_dir();
+    let pid_file = pid_dir.path().join("pid");
+    let command = format!(
+        "{BLOCKING_COMMAND} & echo $! > {}; wait",
+        pid_file.display()
+    );
+    branch.find_regression_commit(case.project_repository.repo(), &target, &command, 1)?;
+
+    let pid = std::fs::read_to_string(&pid_file)?.trim().to_owned();
+    let is_running = || {
+        std::process::Command::new("kill")
+            .args(["-0", &pid])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .map_or(false, |status| status.success())
+    };
+    // The killed process may take a moment to be reaped.
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while is_running() && std::time::Instant::now() < deadline {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    assert!(
+        !is_running(),
+        "background processes of the command are killed as well"
+    );
+    Ok(())
+}
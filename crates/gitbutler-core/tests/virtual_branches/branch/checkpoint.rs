@@ -0,0 +1,204 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::Result;
+use gitbutler_core::virtual_branches::{
+    branch::{BranchCreateRequest, CheckpointId},
+    commit, create_virtual_branch, delete_branch, list_virtual_branches, restore_branch_checkpoint,
+};
+use gitbutler_testsupport::{virtual_branches::set_test_target, Case, Suite};
+
+#[test]
+fn create_list_and_restore() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case_with_files(HashMap::from([(PathBuf::from("file.txt"), "one\n")]));
+    set_test_target(project_repository)?;
+    let repo = project_repository.repo();
+    let vb_state = project.virtual_branches();
+
+    let branch_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+    std::fs::write(project.path.join("file.txt"), "two\n")?;
+    commit(project_repository, branch_id, "two", None, None, false)?;
+    std::fs::write(project.path.join("file.txt"), "three\n")?;
+    list_virtual_branches(project_repository)?;
+
+    let mut branch = vb_state.get_branch(branch_id)?;
+    assert!(branch.list_checkpoints().is_empty());
+    let checkpoint = branch.create_checkpoint(repo, Some("before squash"))?;
+    vb_state.set_branch(branch.clone())?;
+    let (head, tree) = (branch.head, branch.tree);
+
+    assert_eq!(checkpoint.label.as_deref(), Some("before squash"));
+    assert_eq!(checkpoint.stack_head, head);
+    let wip_commit = repo.find_commit(checkpoint.wip_stash.expect("has uncommitted changes"))?;
+    assert_eq!(wip_commit.tree_id(), tree);
+    assert_eq!(wip_commit.parent_id(0)?, head);
+
+    let mut branch = vb_state.get_branch(branch_id)?;
+    assert_eq!(
+        branch.list_checkpoints(),
+        &[checkpoint.clone()],
+        "checkpoints are persisted with the branch"
+    );
+
+    branch.head = repo.find_commit(head)?.parent_id(0)?;
+    branch.tree = repo.find_commit(branch.head)?.tree_id();
+    branch.restore_checkpoint(repo, checkpoint.id)?;
+    assert_eq!(branch.head, head);
+    assert_eq!(branch.tree, tree);
+    Ok(())
+}
+
+#[test]
+fn checkpoint_without_uncommitted_changes() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case();
+    set_test_target(project_repository)?;
+    let repo = project_repository.repo();
+
+    let branch_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+    let mut branch = project.virtual_branches().get_branch(branch_id)?;
+
+    let first = branch.create_checkpoint(repo, None)?;
+    let second = branch.create_checkpoint(repo, Some("second"))?;
+    assert_eq!(first.wip_stash, None);
+    assert_eq!(first.label, None);
+    assert_eq!(branch.list_checkpoints(), &[first.clone(), second]);
+
+    let head_tree = branch.tree;
+    branch.restore_checkpoint(repo, first.id)?;
+    assert_eq!(branch.tree, head_tree);
+    Ok(())
+}
+
+#[test]
+fn restore_unknown_checkpoint() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case();
+    set_test_target(project_repository)?;
+
+    let branch_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+    let mut branch = project.virtual_branches().get_branch(branch_id)?;
+
+    assert!(branch
+        .restore_checkpoint(project_repository.repo(), CheckpointId::generate())
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn checkpoint_commits_are_referenced_until_discarded() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case_with_files(HashMap::from([(PathBuf::from("file.txt"), "one\n")]));
+    set_test_target(project_repository)?;
+    let repo = project_repository.repo();
+
+    let branch_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+    std::fs::write(project.path.join("file.txt"), "two\n")?;
+    list_virtual_branches(project_repository)?;
+    let mut branch = project.virtual_branches().get_branch(branch_id)?;
+
+    let with_changes = branch.create_checkpoint(repo, None)?;
+    let wip_stash = with_changes.wip_stash.expect("has uncommitted changes");
+    assert_eq!(
+        repo.refname_to_id(&with_changes.refname())?,
+        wip_stash,
+        "the WIP commit, and with it the head, is kept alive"
+    );
+
+    branch.tree = repo.find_commit(branch.head)?.tree_id();
+    let without_changes = branch.create_checkpoint(repo, None)?;
+    assert_eq!(repo.refname_to_id(&without_changes.refname())?, branch.head);
+
+    branch.discard_checkpoint(repo, with_changes.id)?;
+    assert!(repo.find_reference(&with_changes.refname()).is_err());
+    assert_eq!(branch.list_checkpoints(), &[without_changes.clone()]);
+    assert!(branch.discard_checkpoint(repo, with_changes.id).is_err());
+    Ok(())
+}
+
+#[test]
+fn restore_updates_the_workspace() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case_with_files(HashMap::from([(PathBuf::from("file.txt"), "one\n")]));
+    set_test_target(project_repository)?;
+    let repo = project_repository.repo();
+    let vb_state = project.virtual_branches();
+
+    let branch_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+    std::fs::write(project.path.join("file.txt"), "two\n")?;
+    commit(project_repository, branch_id, "two", None, None, false)?;
+    std::fs::write(project.path.join("file.txt"), "three\n")?;
+    std::fs::write(project.path.join("wip.txt"), "wip\n")?;
+    list_virtual_branches(project_repository)?;
+
+    let mut branch = vb_state.get_branch(branch_id)?;
+    let checkpoint = branch.create_checkpoint(repo, None)?;
+    vb_state.set_branch(branch.clone())?;
+
+    std::fs::write(project.path.join("file.txt"), "four\n")?;
+    commit(project_repository, branch_id, "four", None, None, false)?;
+    std::fs::write(project.path.join("wip.txt"), "changed\n")?;
+    list_virtual_branches(project_repository)?;
+
+    restore_branch_checkpoint(project_repository, branch_id, checkpoint.id)?;
+    assert_eq!(
+        std::fs::read_to_string(project.path.join("file.txt"))?,
+        "three\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(project.path.join("wip.txt"))?,
+        "wip\n"
+    );
+    let restored = vb_state.get_branch(branch_id)?;
+    assert_eq!(restored.head, checkpoint.stack_head);
+    assert!(
+        repo.head()?
+            .peel_to_commit()?
+            .parent_ids()
+            .any(|id| id == checkpoint.stack_head),
+        "the integration commit builds on the restored head"
+    );
+    Ok(())
+}
+
+#[test]
+fn deleting_a_branch_deletes_its_checkpoint_references() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case();
+    set_test_target(project_repository)?;
+    let repo = project_repository.repo();
+    let vb_state = project.virtual_branches();
+
+    let branch_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+    let mut branch = vb_state.get_branch(branch_id)?;
+    let checkpoint = branch.create_checkpoint(repo, None)?;
+    vb_state.set_branch(branch)?;
+
+    delete_branch(project_repository, branch_id)?;
+    assert!(repo.find_reference(&checkpoint.refname()).is_err());
+    Ok(())
+}
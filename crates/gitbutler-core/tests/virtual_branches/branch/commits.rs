@@ -0,0 +1,49 @@
+use anyhow::Result;
+use gitbutler_core::virtual_branches::{
+    branch::BranchCreateRequest, commit, create_virtual_branch,
+};
+use gitbutler_testsupport::{virtual_branches::set_test_target, Case, Suite};
+
+#[test]
+fn commit_subjects() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case();
+    set_test_target(project_repository)?;
+    let repo = project_repository.repo();
+
+    let branch_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+    let vb_state = project.virtual_branches();
+    let target = vb_state.get_default_target()?;
+    assert!(vb_state
+        .get_branch(branch_id)?
+        .get_commit_subjects(repo, &target)?
+        .is_empty());
+
+    std::fs::write(project.path.join("one.txt"), "one\n")?;
+    commit(project_repository, branch_id, "first", None, None, false)?;
+    std::fs::write(project.path.join("two.txt"), "two\n")?;
+    commit(
+        project_repository,
+        branch_id,
+        "  second subject  \n\nwith a body",
+        None,
+        None,
+        false,
+    )?;
+
+    let branch = vb_state.get_branch(branch_id)?;
+    let subjects = branch.get_commit_subjects(repo, &target)?;
+    let head = repo.find_commit(branch.head)?;
+    assert_eq!(
+        subjects,
+        vec![
+            (head.id(), "second subject".to_string()),
+            (head.parent_id(0)?, "first".to_string()),
+        ]
+    );
+    Ok(())
+}
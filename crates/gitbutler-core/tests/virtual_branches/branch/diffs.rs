@@ -0,0 +1,53 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::Result;
+use gitbutler_core::virtual_branches::{
+    branch::BranchCreateRequest, commit, create_virtual_branch,
+};
+use gitbutler_testsupport::{virtual_branches::set_test_target, Case, Suite};
+
+#[test]
+fn stacked_diff_is_union_of_incremental_diffs() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case_with_files(HashMap::from([(PathBuf::from("file.txt"), "content\n")]));
+    set_test_target(project_repository)?;
+    let repo = project_repository.repo();
+
+    let branch_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+    std::fs::write(project.path.join("a.txt"), "a\n")?;
+    commit(project_repository, branch_id, "add a", None, None, false)?;
+    std::fs::write(project.path.join("b.txt"), "b\n")?;
+    commit(project_repository, branch_id, "add b", None, None, false)?;
+
+    let vb_state = project.virtual_branches();
+    let branch = vb_state.get_branch(branch_id)?;
+    let target = vb_state.get_default_target()?;
+
+    let incremental = branch.get_incremental_diffs(repo, &target, 3)?;
+    let head = repo.find_commit(branch.head)?;
+    assert_eq!(
+        incremental.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+        vec![head.parent_id(0)?, head.id()],
+        "oldest commit first"
+    );
+    assert!(incremental[0]
+        .1
+        .contains("+++ b/a.txt\n@@ -0,0 +1 @@\n+a\n"));
+    assert!(incremental[1]
+        .1
+        .contains("+++ b/b.txt\n@@ -0,0 +1 @@\n+b\n"));
+
+    let stacked = branch.get_stacked_diff(repo, &target, 3)?;
+    assert_eq!(
+        stacked,
+        incremental
+            .into_iter()
+            .map(|(_, patch)| patch)
+            .collect::<String>()
+    );
+    Ok(())
+}
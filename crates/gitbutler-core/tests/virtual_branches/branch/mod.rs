@@ -1,3 +1,10 @@
+mod bisect;
+mod checkpoint;
+mod commits;
+mod diffs;
 mod file_ownership;
 mod hunk;
 mod ownership;
+mod ownership_history;
+mod sensitive_data;
+mod trees;
@@ -2,8 +2,9 @@ use std::{path::PathBuf, vec};
 
 use gitbutler_core::virtual_branches::{
     branch::{reconcile_claims, BranchOwnershipClaims, Hunk, OwnershipClaim},
-    Branch, BranchId,
+    Branch,
 };
+use gitbutler_testsupport::virtual_branches::new_test_branch;
 
 #[test]
 fn reconcile_ownership_simple() {
@@ -28,18 +29,7 @@ fn reconcile_ownership_simple() {
                 ],
             }],
         },
-        applied: true,
-        tree: git2::Oid::zero(),
-        head: git2::Oid::zero(),
-        id: BranchId::default(),
-        notes: String::default(),
-        upstream: None,
-        upstream_head: None,
-        created_timestamp_ms: u128::default(),
-        updated_timestamp_ms: u128::default(),
-        order: usize::default(),
-        selected_for_changes: None,
-        allow_rebasing: true,
+        ..new_test_branch(0)
     };
     let branch_b = Branch {
         name: "b".to_string(),
@@ -54,18 +44,7 @@ fn reconcile_ownership_simple() {
                 }],
             }],
         },
-        applied: true,
-        tree: git2::Oid::zero(),
-        head: git2::Oid::zero(),
-        id: BranchId::default(),
-        notes: String::default(),
-        upstream: None,
-        upstream_head: None,
-        created_timestamp_ms: u128::default(),
-        updated_timestamp_ms: u128::default(),
-        order: usize::default(),
-        selected_for_changes: None,
-        allow_rebasing: true,
+        ..new_test_branch(0)
     };
     let all_branches: Vec<Branch> = vec![branch_a.clone(), branch_b.clone()];
     let claim: Vec<OwnershipClaim> = vec![OwnershipClaim {
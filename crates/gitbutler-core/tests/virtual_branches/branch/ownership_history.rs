@@ -0,0 +1,195 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use gitbutler_core::virtual_branches::{
+    branch::{BranchCreateRequest, OwnershipClaim, MAX_CLOSED_OWNERSHIP_ENTRIES},
+    commit, create_virtual_branch, get_current_file_owner, list_virtual_branches,
+};
+use gitbutler_testsupport::{
+    virtual_branches::{new_test_branch, set_test_target},
+    Case, Suite,
+};
+
+fn claim(value: &str) -> OwnershipClaim {
+    value.parse().unwrap()
+}
+
+#[test]
+fn assign() {
+    let mut branch = new_test_branch(0);
+    branch.assign_hunk(claim("foo:1-2"));
+    branch.assign_hunk(claim("foo:5-6"));
+
+    let history = branch.get_file_ownership_history(Path::new("foo"));
+    assert_eq!(
+        history.len(),
+        1,
+        "assigning more hunks of the same file extends ownership"
+    );
+    assert_eq!(history[0].stack_id, branch.id);
+    assert_eq!(history[0].unassigned_at_ms, None);
+    assert!(branch
+        .get_file_ownership_history(Path::new("bar"))
+        .is_empty());
+}
+
+#[test]
+fn reassign() {
+    let mut a = new_test_branch(0);
+    let mut b = new_test_branch(0);
+    a.assign_hunk(claim("foo:1-2"));
+
+    for taken in a.unassign_hunk(&claim("foo:1-2")) {
+        b.assign_hunk(taken);
+    }
+
+    let history_a = a.get_file_ownership_history(Path::new("foo"));
+    let history_b = b.get_file_ownership_history(Path::new("foo"));
+    assert_eq!(history_a.len(), 1);
+    assert_eq!(history_b.len(), 1);
+    assert_eq!(history_b[0].stack_id, b.id);
+    let released_at = history_a[0].unassigned_at_ms.expect("a gave up ownership");
+    assert!(released_at <= history_b[0].assigned_at_ms);
+    assert_eq!(history_b[0].unassigned_at_ms, None);
+    assert_eq!(b.ownership.to_string(), "foo:1-2\n");
+
+    a.assign_hunk(claim("foo:7-8"));
+    let history_a = a.get_file_ownership_history(Path::new("foo"));
+    assert_eq!(
+        history_a.len(),
+        1,
+        "owning the file again right away continues the previous period"
+    );
+    assert_eq!(history_a[0].unassigned_at_ms, None);
+}
+
+#[test]
+fn owning_a_file_again_later_opens_a_new_period() {
+    let mut branch = new_test_branch(0);
+    branch.assign_hunk(claim("foo:1-2"));
+    branch.unassign_hunk(&claim("foo:1-2"));
+    branch.ownership_log[0].unassigned_at_ms = Some(1);
+
+    branch.assign_hunk(claim("foo:7-8"));
+    let history = branch.get_file_ownership_history(Path::new("foo"));
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].unassigned_at_ms, Some(1));
+    assert_eq!(history[1].unassigned_at_ms, None);
+}
+
+#[test]
+fn closed_periods_are_capped() {
+    let mut branch = new_test_branch(0);
+    let file_count = MAX_CLOSED_OWNERSHIP_ENTRIES + 5;
+    for index in 0..file_count {
+        let claim = claim(&format!("file-{index}:1-2"));
+        branch.assign_hunk(claim.clone());
+        branch.unassign_hunk(&claim);
+    }
+    branch.assign_hunk(claim("open:1-2"));
+
+    assert_eq!(branch.ownership_log.len(), MAX_CLOSED_OWNERSHIP_ENTRIES + 1);
+    assert!(
+        branch
+            .get_file_ownership_history(Path::new("file-0"))
+            .is_empty(),
+        "the oldest periods are dropped"
+    );
+    assert_eq!(
+        branch
+            .get_file_ownership_history(Path::new(&format!("file-{}", file_count - 1)))
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn recomputing_unchanged_ownership_keeps_the_log() {
+    let mut branch = new_test_branch(0);
+    branch.assign_hunk(claim("foo:1-2"));
+    let log = branch.ownership_log.clone();
+
+    for _ in 0..3 {
+        branch.replace_ownership("foo:1-2\n".parse().unwrap());
+        branch.replace_ownership("foo:1-4\n".parse().unwrap());
+    }
+    assert_eq!(branch.ownership_log, log);
+}
+
+#[test]
+fn unassign() {
+    let mut branch = new_test_branch(0);
+    branch.assign_hunk(claim("foo:1-2,5-6"));
+
+    branch.unassign_hunk(&claim("foo:1-2"));
+    assert_eq!(
+        branch.get_file_ownership_history(Path::new("foo"))[0].unassigned_at_ms,
+        None,
+        "the branch still owns a hunk of the file"
+    );
+
+    branch.unassign_hunk(&claim("foo:5-6"));
+    assert!(branch.get_file_ownership_history(Path::new("foo"))[0]
+        .unassigned_at_ms
+        .is_some());
+    assert!(branch.ownership.is_empty());
+}
+
+#[test]
+fn current_file_owner() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case();
+    set_test_target(project_repository)?;
+
+    let branch_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+    assert_eq!(
+        get_current_file_owner(project_repository, Path::new("file.txt"))?,
+        None
+    );
+
+    std::fs::write(project.path.join("file.txt"), "content\n")?;
+    list_virtual_branches(project_repository)?;
+    assert_eq!(
+        get_current_file_owner(project_repository, &PathBuf::from("file.txt"))?,
+        Some(branch_id)
+    );
+
+    let branch = project.virtual_branches().get_branch(branch_id)?;
+    let history = branch.get_file_ownership_history(Path::new("file.txt"));
+    assert_eq!(history.len(), 1, "the ownership log is persisted");
+    Ok(())
+}
+
+#[test]
+fn committing_owned_changes_closes_the_ownership_entry() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case();
+    set_test_target(project_repository)?;
+
+    let branch_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+    std::fs::write(project.path.join("file.txt"), "content\n")?;
+    list_virtual_branches(project_repository)?;
+
+    let branch = project.virtual_branches().get_branch(branch_id)?;
+    let history = branch.get_file_ownership_history(Path::new("file.txt"));
+    assert_eq!(history.len(), 1);
+    assert!(history[0].unassigned_at_ms.is_none());
+
+    commit(project_repository, branch_id, "commit", None, None, false)?;
+    list_virtual_branches(project_repository)?;
+
+    let branch = project.virtual_branches().get_branch(branch_id)?;
+    assert!(branch.ownership.is_empty());
+    let history = branch.get_file_ownership_history(Path::new("file.txt"));
+    assert_eq!(history.len(), 1);
+    assert!(history[0].unassigned_at_ms.is_some());
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::Result;
+use gitbutler_core::virtual_branches::{
+    branch::BranchCreateRequest, commit, create_virtual_branch,
+};
+use gitbutler_testsupport::{virtual_branches::set_test_target, Case, Suite};
+
+#[test]
+fn base_and_head_trees() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case_with_files(HashMap::from([(PathBuf::from("file.txt"), "content\n")]));
+    set_test_target(project_repository)?;
+    let repo = project_repository.repo();
+
+    let branch_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+    std::fs::write(project.path.join("file.txt"), "changed\n")?;
+    commit(project_repository, branch_id, "change", None, None, false)?;
+    std::fs::write(project.path.join("uncommitted.txt"), "wip\n")?;
+
+    let vb_state = project.virtual_branches();
+    let branch = vb_state.get_branch(branch_id)?;
+    let target = vb_state.get_default_target()?;
+
+    let merge_base = repo.merge_base(target.sha, branch.head)?;
+    let expected_base_tree = repo.find_commit(merge_base)?.tree_id();
+    assert_eq!(
+        branch.get_base_tree(repo, &target)?.id(),
+        expected_base_tree
+    );
+
+    let head_tree = branch.get_head_tree(repo)?;
+    assert_eq!(head_tree.id(), repo.find_commit(branch.head)?.tree_id());
+    assert!(
+        head_tree.get_name("uncommitted.txt").is_none(),
+        "uncommitted changes aren't part of the head tree"
+    );
+    Ok(())
+}
+
+#[test]
+fn base_tree_of_branch_without_commits_is_target_tree() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case_with_files(HashMap::from([(PathBuf::from("file.txt"), "content\n")]));
+    set_test_target(project_repository)?;
+    let repo = project_repository.repo();
+
+    let branch_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+    let vb_state = project.virtual_branches();
+    let branch = vb_state.get_branch(branch_id)?;
+    let target = vb_state.get_default_target()?;
+
+    let target_tree = repo.find_commit(target.sha)?.tree_id();
+    assert_eq!(branch.get_base_tree(repo, &target)?.id(), target_tree);
+    assert_eq!(branch.get_head_tree(repo)?.id(), target_tree);
+    Ok(())
+}
@@ -4,47 +4,7 @@ use anyhow::Result;
 use gitbutler_core::virtual_branches;
 use once_cell::sync::Lazy;
 
-use gitbutler_testsupport::{Case, Suite};
-
-static TEST_INDEX: Lazy<AtomicUsize> = Lazy::new(|| AtomicUsize::new(0));
-
-fn new_test_branch() -> virtual_branches::branch::Branch {
-    TEST_INDEX.fetch_add(1, Ordering::Relaxed);
-
-    virtual_branches::branch::Branch {
-        id: virtual_branches::BranchId::generate(),
-        name: format!("branch_name_{}", TEST_INDEX.load(Ordering::Relaxed)),
-        notes: String::new(),
-        applied: true,
-        upstream: Some(
-            format!(
-                "refs/remotes/origin/upstream_{}",
-                TEST_INDEX.load(Ordering::Relaxed)
-            )
-            .parse()
-            .unwrap(),
-        ),
-        upstream_head: None,
-        created_timestamp_ms: TEST_INDEX.load(Ordering::Relaxed) as u128,
-        updated_timestamp_ms: (TEST_INDEX.load(Ordering::Relaxed) + 100) as u128,
-        head: format!(
-            "0123456789abcdef0123456789abcdef0123456{}",
-            TEST_INDEX.load(Ordering::Relaxed)
-        )
-        .parse()
-        .unwrap(),
-        tree: format!(
-            "0123456789abcdef0123456789abcdef012345{}",
-            TEST_INDEX.load(Ordering::Relaxed) + 10
-        )
-        .parse()
-        .unwrap(),
-        ownership: virtual_branches::branch::BranchOwnershipClaims::default(),
-        order: TEST_INDEX.load(Ordering::Relaxed),
-        selected_for_changes: Some(1),
-        allow_rebasing: true,
-    }
-}
+use gitbutler_testsupport::{virtual_branches::new_test_branch, Case, Suite};
 
 static TEST_TARGET_INDEX: Lazy<AtomicUsize> = Lazy::new(|| AtomicUsize::new(0));
 
@@ -88,11 +48,15 @@ fn iterate_all() -> Result<()> {
 
     let vb_state = project.virtual_branches();
     vb_state.set_default_target(new_test_target())?;
-    let branch_1 = new_test_branch();
+    let branch_1 = virtual_branches::Branch {
+        upstream: Some("refs/remotes/origin/upstream".parse().unwrap()),
+        selected_for_changes: Some(1),
+        ..new_test_branch(0)
+    };
     vb_state.set_branch(branch_1.clone())?;
-    let branch_2 = new_test_branch();
+    let branch_2 = new_test_branch(1);
     vb_state.set_branch(branch_2.clone())?;
-    let branch_3 = new_test_branch();
+    let branch_3 = new_test_branch(2);
     vb_state.set_branch(branch_3.clone())?;
 
     let iter = vb_state.list_branches()?;
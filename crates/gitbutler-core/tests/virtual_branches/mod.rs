@@ -1,5 +1,7 @@
 mod branch;
 mod iterator;
+mod state;
+mod sync_from_local_branch;
 
 use std::{
     collections::HashMap,
@@ -0,0 +1,930 @@
+use std::path::Path;
+
+use anyhow::Result;
+use gitbutler_core::{
+    error::Code,
+    git::RemoteRefname,
+    virtual_branches::{
+        branch::{Branch, BranchCreateRequest},
+        create_virtual_branch,
+        target::Target,
+        BranchId, OrderingViolation, VirtualBranchesHandle, VirtualBranchesState,
+        CURRENT_SCHEMA_VERSION,
+    },
+};
+use gitbutler_testsupport::{
+    temp_dir,
+    virtual_branches::{new_test_branch, set_test_target},
+    Case, Suite,
+};
+
+#[test]
+fn valid_ordering() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    assert!(vb_state.validate_ordering()?.is_empty());
+
+    vb_state.set_branch(new_test_branch(1))?;
+    vb_state.set_branch(new_test_branch(0))?;
+    vb_state.set_branch(Branch {
+        applied: false,
+        ..new_test_branch(2)
+    })?;
+    assert!(vb_state.validate_ordering()?.is_empty());
+
+    let unapplied = Branch {
+        applied: false,
+        ..new_test_branch(7)
+    };
+    vb_state.set_branch(unapplied.clone())?;
+    assert_eq!(
+        vb_state.validate_ordering()?,
+        vec![
+            OrderingViolation::GapAt(3),
+            OrderingViolation::OutOfRange {
+                id: unapplied.id,
+                order: 7,
+                max: 3
+            }
+        ],
+        "unapplied branches take part in the ordering"
+    );
+    Ok(())
+}
+
+#[test]
+fn gap() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    vb_state.set_branch(new_test_branch(0))?;
+    let out_of_range = new_test_branch(2);
+    vb_state.set_branch(out_of_range.clone())?;
+
+    assert_eq!(
+        vb_state.validate_ordering()?,
+        vec![
+            OrderingViolation::GapAt(1),
+            OrderingViolation::OutOfRange {
+                id: out_of_range.id,
+                order: 2,
+                max: 1
+            }
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn duplicate() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    let a = new_test_branch(0);
+    let b = new_test_branch(0);
+    vb_state.set_branch(a.clone())?;
+    vb_state.set_branch(b.clone())?;
+
+    let mut ids = vec![a.id, b.id];
+    ids.sort();
+    assert_eq!(
+        vb_state.validate_ordering()?,
+        vec![
+            OrderingViolation::Duplicate(0, ids),
+            OrderingViolation::GapAt(1)
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn out_of_range() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    let branch = new_test_branch(5);
+    vb_state.set_branch(branch.clone())?;
+
+    assert_eq!(
+        vb_state.validate_ordering()?,
+        vec![
+            OrderingViolation::GapAt(0),
+            OrderingViolation::OutOfRange {
+                id: branch.id,
+                order: 5,
+                max: 0
+            }
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn repair_ordering() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    let first = new_test_branch(0);
+    let second = new_test_branch(3);
+    let third = new_test_branch(3);
+    let unapplied = Branch {
+        applied: false,
+        ..new_test_branch(9)
+    };
+    for branch in [&first, &second, &third, &unapplied] {
+        vb_state.set_branch(branch.clone())?;
+    }
+
+    vb_state.repair_ordering()?;
+    assert!(vb_state.validate_ordering()?.is_empty());
+    assert_eq!(vb_state.get_branch(first.id)?.order, 0);
+    assert_eq!(
+        vb_state.get_branch(second.id)?.order,
+        1,
+        "ties are broken by creation time"
+    );
+    assert_eq!(vb_state.get_branch(third.id)?.order, 2);
+    assert_eq!(vb_state.get_branch(unapplied.id)?.order, 3);
+    Ok(())
+}
+
+#[test]
+fn concurrent_writes_are_not_lost() -> Result<()> {
+    let dir = temp_dir();
+    let writers = (0..2)
+        .map(|_| {
+            let base_path = dir.path().to_owned();
+            std::thread::spawn(move || -> Result<Vec<BranchId>> {
+                let vb_state = VirtualBranchesHandle::new(base_path);
+                (0..20)
+                    .map(|order| {
+                        let branch = new_test_branch(order);
+                        vb_state.set_branch(branch.clone())?;
+                        Ok(branch.id)
+                    })
+                    .collect()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut written = writers
+        .into_iter()
+        .map(|writer| writer.join().unwrap())
+        .collect::<Result<Vec<_>>>()?
+        .concat();
+    written.sort();
+
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    let mut stored = vb_state
+        .list_branches()?
+        .into_iter()
+        .map(|branch| branch.id)
+        .collect::<Vec<_>>();
+    stored.sort();
+    assert_eq!(stored, written);
+    Ok(())
+}
+
+#[test]
+fn concurrent_atomic_updates_are_not_lost() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    let branch = new_test_branch(0);
+    vb_state.set_branch(branch.clone())?;
+
+    let updaters = (0..4)
+        .map(|_| {
+            let base_path = dir.path().to_owned();
+            std::thread::spawn(move || -> Result<()> {
+                let vb_state = VirtualBranchesHandle::new(base_path);
+                for _ in 0..10 {
+                    vb_state.atomic_update(|virtual_branches| {
+                        let branch = virtual_branches.branches.get_mut(&branch.id).unwrap();
+                        branch.updated_timestamp_ms += 1;
+                        Ok(())
+                    })?;
+                }
+                Ok(())
+            })
+        })
+        .collect::<Vec<_>>();
+    for updater in updaters {
+        updater.join().unwrap()?;
+    }
+
+    assert_eq!(
+        vb_state.get_branch(branch.id)?.updated_timestamp_ms,
+        branch.updated_timestamp_ms + 40
+    );
+    Ok(())
+}
+
+#[test]
+fn replace_state() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    vb_state.set_branch(new_test_branch(0))?;
+
+    let branch = new_test_branch(0);
+    let state = VirtualBranchesState::builder()
+        .branch(branch.clone())
+        .build();
+    vb_state.replace_state(&state)?;
+    assert_eq!(vb_state.list_branches()?, vec![branch.clone()]);
+
+    std::fs::write(dir.path().join("virtual_branches.toml"), "not toml")?;
+    vb_state.replace_state(&state)?;
+    assert_eq!(
+        vb_state.list_branches()?,
+        vec![branch],
+        "unreadable state can be replaced"
+    );
+    Ok(())
+}
+
+#[test]
+fn replacing_state_is_serialized_with_atomic_updates() -> Result<()> {
+    let dir = temp_dir();
+    let replacement = new_test_branch(0);
+
+    let updaters = (0..4)
+        .map(|_| {
+            let base_path = dir.path().to_owned();
+            std::thread::spawn(move || -> Result<()> {
+                let vb_state = VirtualBranchesHandle::new(base_path);
+                for order in 0..10 {
+                    let branch = new_test_branch(order);
+                    vb_state.atomic_update(|virtual_branches| {
+                        virtual_branches.branches.insert(branch.id, branch);
+                        Ok(())
+                    })?;
+                }
+                Ok(())
+            })
+        })
+        .collect::<Vec<_>>();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    vb_state.replace_state(
+        &VirtualBranchesState::builder()
+            .branch(replacement.clone())
+            .build(),
+    )?;
+    for updater in updaters {
+        updater.join().unwrap()?;
+    }
+
+    assert!(
+        vb_state.try_branch(replacement.id)?.is_some(),
+        "no update that started before the replacement wrote over it"
+    );
+    Ok(())
+}
+
+#[test]
+fn failed_atomic_update_is_not_written() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    let branch = new_test_branch(0);
+    vb_state.set_branch(branch.clone())?;
+
+    let result = vb_state.atomic_update(|virtual_branches| {
+        virtual_branches.branches.clear();
+        anyhow::bail!("aborted")
+    });
+    assert!(result.is_err());
+    assert_eq!(vb_state.list_branches()?, vec![branch]);
+    Ok(())
+}
+
+#[test]
+fn find_by_name() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    let applied = new_test_branch(0);
+    let unapplied = Branch {
+        applied: false,
+        ..new_test_branch(1)
+    };
+    vb_state.set_branch(applied.clone())?;
+    vb_state.set_branch(unapplied.clone())?;
+
+    assert_eq!(vb_state.find_by_name(&applied.name)?, Some(applied.clone()));
+    assert_eq!(
+        vb_state.find_by_name(&unapplied.name)?,
+        Some(unapplied.clone())
+    );
+    assert_eq!(vb_state.find_by_name("missing")?, None);
+
+    assert_eq!(
+        vb_state.find_by_name_in_workspace(&applied.name)?,
+        Some(applied)
+    );
+    assert_eq!(vb_state.find_by_name_in_workspace(&unapplied.name)?, None);
+    assert_eq!(vb_state.find_by_name_in_workspace("missing")?, None);
+    Ok(())
+}
+
+#[test]
+fn find_by_name_collision() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    let applied = new_test_branch(0);
+    let unapplied = Branch {
+        name: applied.name.clone(),
+        applied: false,
+        ..new_test_branch(1)
+    };
+    vb_state.set_branch(applied.clone())?;
+    vb_state.set_branch(unapplied)?;
+
+    assert!(vb_state.find_by_name(&applied.name).is_err());
+    assert_eq!(
+        vb_state.find_by_name_in_workspace(&applied.name)?,
+        Some(applied),
+        "only one of the branches is in the workspace"
+    );
+    Ok(())
+}
+
+#[test]
+fn json_round_trip() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    let branches = vec![new_test_branch(0), new_test_branch(1)];
+    for branch in &branches {
+        vb_state.set_branch(branch.clone())?;
+    }
+    let json = vb_state.export_to_json()?;
+
+    let other_dir = temp_dir();
+    let other_state = VirtualBranchesHandle::new(other_dir.path());
+    other_state.import_from_json(&json)?;
+
+    let mut imported = other_state.list_branches()?;
+    imported.sort_by_key(|branch| branch.order);
+    assert_eq!(imported, branches);
+    Ok(())
+}
+
+#[test]
+fn json_import_merges_by_id() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    let exported = new_test_branch(0);
+    vb_state.set_branch(exported.clone())?;
+    let json = vb_state.export_to_json()?;
+
+    let other_dir = temp_dir();
+    let other_state = VirtualBranchesHandle::new(other_dir.path());
+    let local_only = new_test_branch(1);
+    other_state.set_branch(local_only.clone())?;
+    other_state.set_branch(Branch {
+        name: "outdated".to_string(),
+        ..exported.clone()
+    })?;
+    other_state.import_from_json(&json)?;
+
+    assert_eq!(other_state.get_branch(exported.id)?, exported);
+    assert_eq!(other_state.get_branch(local_only.id)?, local_only);
+    assert_eq!(other_state.list_branches()?.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn invalid_json_is_rejected() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    let branch = new_test_branch(0);
+    vb_state.set_branch(branch.clone())?;
+
+    assert!(vb_state.import_from_json("{").is_err());
+    assert_eq!(vb_state.list_branches()?, vec![branch]);
+    Ok(())
+}
+
+#[test]
+fn rename_branch() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case();
+    set_test_target(project_repository)?;
+    let repo = project_repository.repo();
+    let vb_state = project.virtual_branches();
+    let branch_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+    let branch = vb_state.get_branch(branch_id)?;
+
+    vb_state.rename_branch(project_repository, branch.id, "new name!".to_string())?;
+    let renamed = vb_state.get_branch(branch.id)?;
+    assert_eq!(renamed.name, "new name!");
+    assert_eq!(renamed.refname().to_string(), "refs/gitbutler/new-name-");
+    assert!(repo.find_reference(&branch.refname().to_string()).is_err());
+    assert_eq!(
+        repo.find_reference(&renamed.refname().to_string())?
+            .target(),
+        Some(branch.head),
+        "the reference moves along with the name"
+    );
+
+    vb_state.rename_branch(project_repository, branch.id, "new name!".to_string())?;
+    assert_eq!(
+        vb_state.get_branch(branch.id)?.name,
+        "new name!",
+        "renaming a branch to its own name is fine"
+    );
+    assert!(repo.find_reference(&renamed.refname().to_string()).is_ok());
+    Ok(())
+}
+
+#[test]
+fn rename_branch_to_taken_name() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case();
+    set_test_target(project_repository)?;
+    let vb_state = project.virtual_branches();
+    let branch_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+    let other_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+    let branch = vb_state.get_branch(branch_id)?;
+    let other = vb_state.get_branch(other_id)?;
+
+    vb_state.rename_branch(project_repository, branch.id, other.name.clone())?;
+    let renamed = vb_state.get_branch(branch.id)?;
+    assert_eq!(
+        renamed.name,
+        format!("{} 1", other.name),
+        "the name is made unique like in `update_branch`"
+    );
+    let repo = project_repository.repo();
+    assert!(repo.find_reference(&branch.refname().to_string()).is_err());
+    assert!(repo.find_reference(&renamed.refname().to_string()).is_ok());
+    assert!(repo.find_reference(&other.refname().to_string()).is_ok());
+    Ok(())
+}
+
+#[test]
+fn rename_missing_branch() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case();
+    assert!(project
+        .virtual_branches()
+        .rename_branch(project_repository, BranchId::generate(), "name".to_string())
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn from_snapshot() -> Result<()> {
+    let applied = new_test_branch(0);
+    let unapplied = Branch {
+        applied: false,
+        ..new_test_branch(1)
+    };
+    let vb_state = VirtualBranchesHandle::from_snapshot(
+        VirtualBranchesState::builder()
+            .branch(applied.clone())
+            .branch(unapplied.clone())
+            .build(),
+    )?;
+
+    assert_eq!(vb_state.get_branch(applied.id)?, applied);
+    assert_eq!(vb_state.get_branch(unapplied.id)?, unapplied);
+    assert!(vb_state.get_default_target().is_err());
+
+    vb_state.remove_branch(unapplied.id)?;
+    assert_eq!(vb_state.list_branches()?, vec![applied]);
+    Ok(())
+}
+
+#[test]
+fn list_by_remote() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    assert!(vb_state.list_by_remote("origin")?.is_empty());
+
+    let on_origin = Branch {
+        upstream: Some(RemoteRefname::new("origin", "feature")),
+        ..new_test_branch(0)
+    };
+    let on_fork = Branch {
+        upstream: Some(RemoteRefname::new("fork", "origin")),
+        ..new_test_branch(1)
+    };
+    let nested_on_origin = Branch {
+        upstream: Some(RemoteRefname::new("origin", "fork/feature")),
+        applied: false,
+        ..new_test_branch(2)
+    };
+    let without_upstream = new_test_branch(3);
+    for branch in [&on_origin, &on_fork, &nested_on_origin, &without_upstream] {
+        vb_state.set_branch(branch.clone())?;
+    }
+
+    let mut origin = vb_state.list_by_remote("origin")?;
+    origin.sort_by_key(|branch| branch.order);
+    assert_eq!(origin, vec![on_origin, nested_on_origin]);
+    assert_eq!(vb_state.list_by_remote("fork")?, vec![on_fork]);
+    assert!(vb_state.list_by_remote("upstream")?.is_empty());
+    assert!(
+        vb_state
+            .list_by_remote("")
+            .is_ok_and(|branches| branches.is_empty()),
+        "branches without upstream never match"
+    );
+    Ok(())
+}
+
+fn new_test_target() -> Target {
+    Target {
+        branch: "refs/remotes/origin/master".parse().unwrap(),
+        remote_url: "https://example.com/repo.git".to_string(),
+        sha: git2::Oid::zero(),
+        push_remote_name: None,
+    }
+}
+
+#[test]
+fn branch_target() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    let branch = new_test_branch(0);
+    vb_state.set_branch(branch.clone())?;
+    assert_eq!(vb_state.get_branch_target(branch.id)?, None);
+
+    vb_state.set_branch_target(branch.id, new_test_target())?;
+    assert_eq!(
+        vb_state.get_branch_target(branch.id)?,
+        Some(new_test_target())
+    );
+    Ok(())
+}
+
+#[test]
+fn branch_target_of_missing_branch() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    let id = BranchId::generate();
+
+    assert!(vb_state.set_branch_target(id, new_test_target()).is_err());
+    assert_eq!(vb_state.get_branch_target(id)?, None);
+    Ok(())
+}
+
+#[test]
+fn iter_branches() {
+    let applied = new_test_branch(0);
+    let unapplied = Branch {
+        applied: false,
+        ..new_test_branch(1)
+    };
+    let state = VirtualBranchesState::builder()
+        .branch(applied.clone())
+        .branch(unapplied.clone())
+        .build();
+
+    let mut all = state.iter_branches().collect::<Vec<_>>();
+    all.sort_by_key(|branch| branch.order);
+    assert_eq!(all, vec![&applied, &unapplied]);
+    assert_eq!(
+        state.iter_branches_in_workspace().collect::<Vec<_>>(),
+        vec![&applied]
+    );
+}
+
+fn orders(vb_state: &VirtualBranchesHandle, ids: &[BranchId]) -> Result<Vec<usize>> {
+    ids.iter()
+        .map(|id| Ok(vb_state.get_branch(*id)?.order))
+        .collect()
+}
+
+#[test]
+fn reorder_branches() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    let ids = (0..3)
+        .map(|order| {
+            let branch = new_test_branch(order);
+            vb_state.set_branch(branch.clone())?;
+            Ok(branch.id)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    vb_state.reorder_branches(&[ids[2], ids[0], ids[1]])?;
+    assert_eq!(orders(&vb_state, &ids)?, vec![1, 2, 0]);
+    assert!(vb_state.validate_ordering()?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn partial_reorder_keeps_others_in_order() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    let ids = (0..4)
+        .map(|order| {
+            let branch = new_test_branch(order);
+            vb_state.set_branch(branch.clone())?;
+            Ok(branch.id)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    vb_state.reorder_branches(&[ids[3]])?;
+    assert_eq!(orders(&vb_state, &ids)?, vec![1, 2, 3, 0]);
+    Ok(())
+}
+
+#[test]
+fn reorder_branches_rejects_invalid_ids() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    let ids = (0..2)
+        .map(|order| {
+            let branch = new_test_branch(order);
+            vb_state.set_branch(branch.clone())?;
+            Ok(branch.id)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    assert!(vb_state.reorder_branches(&[ids[1], ids[1]]).is_err());
+    assert!(vb_state
+        .reorder_branches(&[ids[1], BranchId::generate()])
+        .is_err());
+    assert_eq!(
+        orders(&vb_state, &ids)?,
+        vec![0, 1],
+        "nothing changes on error"
+    );
+    Ok(())
+}
+
+#[test]
+fn clone_branch() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    let source = Branch {
+        notes: "some notes".to_string(),
+        upstream: Some(RemoteRefname::new("origin", "feature")),
+        selected_for_changes: Some(1),
+        ..new_test_branch(0)
+    };
+    vb_state.set_branch(source.clone())?;
+    vb_state.set_branch_target(source.id, new_test_target())?;
+
+    let clone_id = vb_state.clone_branch(source.id, "copy".to_string())?;
+    assert_ne!(clone_id, source.id);
+    let clone = vb_state.get_branch(clone_id)?;
+    assert_eq!(clone.name, "copy");
+    assert!(!clone.applied);
+    assert_eq!(clone.selected_for_changes, None);
+    assert_eq!(clone.notes, source.notes);
+    assert_eq!(clone.upstream, source.upstream);
+    assert_eq!(clone.order, source.order);
+    assert_eq!((clone.head, clone.tree), (source.head, source.tree));
+    assert_eq!(
+        vb_state.get_branch_target(clone_id)?,
+        Some(new_test_target())
+    );
+    assert_eq!(
+        vb_state.get_branch(source.id)?,
+        source,
+        "the source is unchanged"
+    );
+    Ok(())
+}
+
+#[test]
+fn clone_branch_to_taken_name() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    let source = new_test_branch(0);
+    vb_state.set_branch(source.clone())?;
+
+    let err = vb_state
+        .clone_branch(source.id, source.name.clone())
+        .unwrap_err();
+    assert_eq!(err.downcast_ref::<Code>(), Some(&Code::DuplicateBranchName));
+    assert!(vb_state
+        .clone_branch(BranchId::generate(), "copy".to_string())
+        .is_err());
+    assert_eq!(vb_state.list_branches()?, vec![source]);
+    Ok(())
+}
+
+const V0_STATE: &str = r#"
+[branch_targets]
+
+[branches.0a3b4c5d-6e7f-4a1b-8c2d-3e4f5a6b7c8d]
+id = "0a3b4c5d-6e7f-4a1b-8c2d-3e4f5a6b7c8d"
+name = "old branch"
+notes = ""
+applied = true
+created_timestamp_ms = "1000"
+updated_timestamp_ms = "2000"
+tree = "0000000000000000000000000000000000000000"
+head = "0000000000000000000000000000000000000000"
+ownership = "file.txt:1-5"
+order = 0
+"#;
+
+#[test]
+fn migrates_v0_state() -> Result<()> {
+    let dir = temp_dir();
+    std::fs::write(dir.path().join("virtual_branches.toml"), V0_STATE)?;
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+
+    let branch = vb_state.list_branches()?.pop().unwrap();
+    assert_eq!(branch.name, "old branch");
+    let history = branch.get_file_ownership_history(Path::new("file.txt"));
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].stack_id, branch.id);
+    assert_eq!(history[0].assigned_at_ms, 2000);
+    assert_eq!(history[0].unassigned_at_ms, None);
+
+    vb_state.set_branch(branch.clone())?;
+    let stored = std::fs::read_to_string(dir.path().join("virtual_branches.toml"))?;
+    assert!(stored.contains(&format!("schema_version = {CURRENT_SCHEMA_VERSION}")));
+    assert_eq!(
+        vb_state.get_branch(branch.id)?,
+        branch,
+        "migrating again doesn't change anything"
+    );
+    Ok(())
+}
+
+#[test]
+fn state_from_newer_version_is_rejected() -> Result<()> {
+    let dir = temp_dir();
+    let state_path = dir.path().join("virtual_branches.toml");
+    let newer_state = format!(
+        "schema_version = {}\n{V0_STATE}",
+        CURRENT_SCHEMA_VERSION + 1
+    );
+    std::fs::write(&state_path, &newer_state)?;
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+
+    assert!(vb_state.list_branches().is_err());
+    assert!(vb_state.set_branch(new_test_branch(1)).is_err());
+    assert_eq!(
+        std::fs::read_to_string(&state_path)?,
+        newer_state,
+        "the newer state is left untouched"
+    );
+    Ok(())
+}
+
+#[test]
+fn state_from_toml_is_migrated() -> Result<()> {
+    let state = VirtualBranchesState::from_toml(V0_STATE)?;
+    assert_eq!(state.schema_version, CURRENT_SCHEMA_VERSION);
+    let branch = state.branches.values().next().unwrap();
+    assert_eq!(
+        branch
+            .get_file_ownership_history(Path::new("file.txt"))
+            .len(),
+        1
+    );
+
+    let newer_state = format!(
+        "schema_version = {}\n{V0_STATE}",
+        CURRENT_SCHEMA_VERSION + 1
+    );
+    assert!(VirtualBranchesState::from_toml(&newer_state).is_err());
+    Ok(())
+}
+
+#[test]
+fn export_gitconfig_snippet() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    assert_eq!(vb_state.export_gitconfig_snippet()?, "");
+
+    let second = Branch {
+        name: "second branch".to_string(),
+        upstream: Some(RemoteRefname::new("fork", "second")),
+        ..new_test_branch(1)
+    };
+    let first = Branch {
+        name: "first".to_string(),
+        upstream: Some(RemoteRefname::new("origin", "feature/first")),
+        ..new_test_branch(0)
+    };
+    let unapplied = Branch {
+        applied: false,
+        upstream: Some(RemoteRefname::new("origin", "unapplied")),
+        ..new_test_branch(2)
+    };
+    for branch in [&second, &first, &unapplied, &new_test_branch(3)] {
+        vb_state.set_branch(branch.clone())?;
+    }
+
+    let snippet = vb_state.export_gitconfig_snippet()?;
+    assert_eq!(
+        snippet,
+        "[branch \"first\"]\n\tremote = origin\n\tmerge = refs/heads/feature/first\n\
+         [branch \"second-branch\"]\n\tremote = fork\n\tmerge = refs/heads/second\n"
+    );
+
+    let config_path = dir.path().join("config");
+    std::fs::write(&config_path, &snippet)?;
+    let config = git2::Config::open(&config_path)?;
+    assert_eq!(config.get_string("branch.first.remote")?, "origin");
+    assert_eq!(
+        config.get_string("branch.second-branch.merge")?,
+        "refs/heads/second"
+    );
+    Ok(())
+}
+
+#[test]
+fn merge_from_handle() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    let other_dir = temp_dir();
+    let other_state = VirtualBranchesHandle::new(other_dir.path());
+
+    let ours_only = new_test_branch(0);
+    let theirs_only = new_test_branch(1);
+    let ours_newer = new_test_branch(2);
+    let theirs_newer = new_test_branch(3);
+    vb_state.set_branch(ours_only.clone())?;
+    vb_state.set_branch(ours_newer.clone())?;
+    vb_state.set_branch(theirs_newer.clone())?;
+
+    let theirs_newer = Branch {
+        name: "updated elsewhere".to_string(),
+        updated_timestamp_ms: theirs_newer.updated_timestamp_ms + 1,
+        ..theirs_newer
+    };
+    other_state.set_branch(theirs_only.clone())?;
+    other_state.set_branch(Branch {
+        name: "outdated".to_string(),
+        updated_timestamp_ms: ours_newer.updated_timestamp_ms - 1,
+        ..ours_newer.clone()
+    })?;
+    other_state.set_branch(theirs_newer.clone())?;
+    other_state.set_branch_target(theirs_only.id, new_test_target())?;
+
+    let mut expected = vec![theirs_only.id, theirs_newer.id];
+    expected.sort();
+    assert_eq!(vb_state.merge_from_handle(&other_state)?, expected);
+
+    assert_eq!(vb_state.get_branch(ours_only.id)?, ours_only);
+    assert_eq!(vb_state.get_branch(theirs_only.id)?, theirs_only);
+    assert_eq!(vb_state.get_branch(ours_newer.id)?, ours_newer);
+    assert_eq!(vb_state.get_branch(theirs_newer.id)?, theirs_newer);
+    assert_eq!(
+        vb_state.get_branch_target(theirs_only.id)?,
+        Some(new_test_target())
+    );
+    assert!(
+        vb_state.merge_from_handle(&other_state)?.is_empty(),
+        "merging again takes nothing"
+    );
+    Ok(())
+}
+
+#[test]
+fn branch_counts() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    assert_eq!(vb_state.branch_count()?, 0);
+    assert_eq!(vb_state.workspace_branch_count()?, 0);
+
+    vb_state.set_branch(new_test_branch(0))?;
+    vb_state.set_branch(new_test_branch(1))?;
+    vb_state.set_branch(Branch {
+        applied: false,
+        ..new_test_branch(2)
+    })?;
+    assert_eq!(vb_state.branch_count()?, 3);
+    assert_eq!(vb_state.workspace_branch_count()?, 2);
+    Ok(())
+}
+
+#[test]
+fn compact_branch_targets() -> Result<()> {
+    let dir = temp_dir();
+    let vb_state = VirtualBranchesHandle::new(dir.path());
+    assert_eq!(vb_state.compact_branch_targets()?, 0);
+
+    let kept = new_test_branch(0);
+    let removed = new_test_branch(1);
+    for branch in [&kept, &removed] {
+        vb_state.set_branch(branch.clone())?;
+        vb_state.set_branch_target(branch.id, new_test_target())?;
+    }
+    vb_state.remove_branch(removed.id)?;
+
+    assert_eq!(vb_state.compact_branch_targets()?, 1);
+    assert_eq!(vb_state.get_branch_target(removed.id)?, None);
+    assert_eq!(
+        vb_state.get_branch_target(kept.id)?,
+        Some(new_test_target())
+    );
+    assert_eq!(vb_state.compact_branch_targets()?, 0);
+    Ok(())
+}
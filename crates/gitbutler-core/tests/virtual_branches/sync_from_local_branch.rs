@@ -0,0 +1,176 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::Result;
+use gitbutler_core::{
+    git::LocalRefname,
+    virtual_branches::{
+        branch::BranchCreateRequest, commit, create_virtual_branch, sync_branch_from_local_branch,
+    },
+};
+use gitbutler_testsupport::{virtual_branches::set_test_target, Case, Suite};
+
+/// Creates `refs/heads/<name>` with one commit per file on top of `parent`.
+fn local_branch_with_files(
+    repo: &git2::Repository,
+    name: &str,
+    parent: git2::Oid,
+    files: &[(&str, &str)],
+) -> Result<Vec<git2::Oid>> {
+    let signature = git2::Signature::now("test", "test@email.com")?;
+    let mut parent = repo.find_commit(parent)?;
+    let mut commits = Vec::new();
+    for (path, content) in files {
+        let blob = repo.blob(content.as_bytes())?;
+        let mut builder = repo.treebuilder(Some(&parent.tree()?))?;
+        builder.insert(path, blob, git2::FileMode::Blob.into())?;
+        let tree = repo.find_tree(builder.write()?)?;
+        let oid = repo.commit(None, &signature, &signature, path, &tree, &[&parent])?;
+        parent = repo.find_commit(oid)?;
+        commits.push(oid);
+    }
+    repo.reference(&format!("refs/heads/{name}"), parent.id(), true, "test")?;
+    Ok(commits)
+}
+
+#[test]
+fn empty_branch_takes_over_local_commits() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case_with_files(HashMap::from([(PathBuf::from("base.txt"), "base\n")]));
+    set_test_target(project_repository)?;
+    let repo = project_repository.repo();
+    let vb_state = project.virtual_branches();
+    let target = vb_state.get_default_target()?;
+
+    let commits = local_branch_with_files(
+        repo,
+        "feature",
+        target.sha,
+        &[("a.txt", "a\n"), ("b.txt", "b\n")],
+    )?;
+    let branch_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+
+    let branch = sync_branch_from_local_branch(project_repository, branch_id, "feature")?;
+    assert_eq!(
+        branch.head, commits[1],
+        "the local commits are used as they are"
+    );
+    assert_eq!(vb_state.get_branch(branch_id)?.head, commits[1]);
+    assert_eq!(
+        vb_state.get_branch(branch_id)?.source_refname,
+        Some(LocalRefname::new("feature", None)),
+        "the local branch is recorded as source"
+    );
+    assert_eq!(std::fs::read_to_string(project.path.join("b.txt"))?, "b\n");
+    Ok(())
+}
+
+#[test]
+fn branch_with_commits_cherry_picks_local_commits() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case_with_files(HashMap::from([(PathBuf::from("base.txt"), "base\n")]));
+    set_test_target(project_repository)?;
+    let repo = project_repository.repo();
+    let vb_state = project.virtual_branches();
+    let target = vb_state.get_default_target()?;
+
+    let commits = local_branch_with_files(repo, "feature", target.sha, &[("a.txt", "a\n")])?;
+    let branch_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+    std::fs::write(project.path.join("own.txt"), "own\n")?;
+    commit(project_repository, branch_id, "own work", None, None, false)?;
+    let own_head = vb_state.get_branch(branch_id)?.head;
+
+    let branch = sync_branch_from_local_branch(project_repository, branch_id, "feature")?;
+    assert_ne!(
+        branch.head, commits[0],
+        "the local commit was cherry-picked"
+    );
+    let head_commit = repo.find_commit(branch.head)?;
+    assert_eq!(head_commit.parent_id(0)?, own_head);
+    assert_eq!(head_commit.message(), Some("a.txt"));
+    let head_tree = head_commit.tree()?;
+    assert!(head_tree.get_name("a.txt").is_some());
+    assert!(head_tree.get_name("own.txt").is_some());
+    assert_eq!(
+        branch.source_refname,
+        Some(LocalRefname::new("feature", None))
+    );
+    Ok(())
+}
+
+#[test]
+fn syncing_twice_is_a_noop() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case_with_files(HashMap::from([(PathBuf::from("base.txt"), "base\n")]));
+    set_test_target(project_repository)?;
+    let repo = project_repository.repo();
+    let vb_state = project.virtual_branches();
+    let target = vb_state.get_default_target()?;
+
+    let commits = local_branch_with_files(repo, "feature", target.sha, &[("a.txt", "a\n")])?;
+    let branch_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+
+    sync_branch_from_local_branch(project_repository, branch_id, "feature")?;
+    let branch = sync_branch_from_local_branch(project_repository, branch_id, "feature")?;
+    assert_eq!(branch.head, commits[0]);
+    assert_eq!(vb_state.get_branch(branch_id)?.head, commits[0]);
+
+    repo.reference("refs/heads/copy", commits[0], false, "test")?;
+    let branch = sync_branch_from_local_branch(project_repository, branch_id, "copy")?;
+    assert_eq!(branch.head, commits[0]);
+    assert_eq!(
+        vb_state.get_branch(branch_id)?.source_refname,
+        Some(LocalRefname::new("copy", None)),
+        "the source is recorded even if there is nothing to bring in"
+    );
+    Ok(())
+}
+
+#[test]
+fn syncing_cherry_picked_commits_twice_is_a_noop() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project,
+        project_repository,
+        ..
+    } = &suite.new_case_with_files(HashMap::from([(PathBuf::from("base.txt"), "base\n")]));
+    set_test_target(project_repository)?;
+    let repo = project_repository.repo();
+    let vb_state = project.virtual_branches();
+    let target = vb_state.get_default_target()?;
+
+    local_branch_with_files(repo, "feature", target.sha, &[("a.txt", "a\n")])?;
+    let branch_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+    std::fs::write(project.path.join("own.txt"), "own\n")?;
+    commit(project_repository, branch_id, "own work", None, None, false)?;
+
+    let first = sync_branch_from_local_branch(project_repository, branch_id, "feature")?;
+    let second = sync_branch_from_local_branch(project_repository, branch_id, "feature")?;
+    assert_eq!(second.head, first.head, "no commits are picked twice");
+    assert_eq!(vb_state.get_branch(branch_id)?.head, first.head);
+    Ok(())
+}
+
+#[test]
+fn missing_local_branch() -> Result<()> {
+    let suite = Suite::default();
+    let Case {
+        project_repository, ..
+    } = &suite.new_case();
+    set_test_target(project_repository)?;
+    let branch_id = create_virtual_branch(project_repository, &BranchCreateRequest::default())?.id;
+
+    assert!(sync_branch_from_local_branch(project_repository, branch_id, "missing").is_err());
+    Ok(())
+}
@@ -18,10 +18,39 @@ pub mod paths {
 }
 
 pub mod virtual_branches {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
     use gitbutler_core::{project_repository, virtual_branches};
 
     use crate::empty_bare_repository;
 
+    static TEST_BRANCH_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+    /// Returns an applied branch at position `order` with a unique name and no commits,
+    /// for tests that don't need it to exist in a repository.
+    pub fn new_test_branch(order: usize) -> virtual_branches::Branch {
+        let index = TEST_BRANCH_INDEX.fetch_add(1, Ordering::Relaxed);
+        virtual_branches::Branch {
+            id: virtual_branches::BranchId::generate(),
+            name: format!("branch_name_{index}"),
+            notes: String::new(),
+            applied: true,
+            upstream: None,
+            upstream_head: None,
+            created_timestamp_ms: index as u128,
+            updated_timestamp_ms: index as u128,
+            tree: git2::Oid::zero(),
+            head: git2::Oid::zero(),
+            ownership: virtual_branches::branch::BranchOwnershipClaims::default(),
+            order,
+            selected_for_changes: None,
+            allow_rebasing: true,
+            checkpoints: vec![],
+            ownership_log: vec![],
+            source_refname: None,
+        }
+    }
+
     pub fn set_test_target(
         project_repository: &project_repository::Repository,
     ) -> anyhow::Result<()> {
@@ -15,6 +15,8 @@ pub(super) enum InternalEvent {
     ProjectFilesChange(ProjectId, Vec<PathBuf>),
     // Triggered on change in the `.git/gitbutler` directory
     GitButlerOplogChange(ProjectId),
+    // Triggered when the contents of `.git/gitbutler/virtual_branches.toml` change, e.g. by another process
+    GitButlerVirtualBranchesChange(ProjectId),
 }
 
 /// This type captures all operations that can be fed into a watcher that runs in the background.
@@ -57,6 +59,9 @@ impl Display for InternalEvent {
             InternalEvent::GitButlerOplogChange(project_id) => {
                 write!(f, "GitButlerOplogChange({})", project_id)
             }
+            InternalEvent::GitButlerVirtualBranchesChange(project_id) => {
+                write!(f, "GitButlerVirtualBranchesChange({})", project_id)
+            }
             InternalEvent::ProjectFilesChange(project_id, paths) => {
                 write!(
                     f,
@@ -6,6 +6,7 @@ use crate::events::InternalEvent;
 use anyhow::{anyhow, Context, Result};
 use gitbutler_core::ops::OPLOG_FILE_NAME;
 use gitbutler_core::projects::ProjectId;
+use gitbutler_core::virtual_branches::VIRTUAL_BRANCHES_FILE_NAME;
 use gitbutler_notify_debouncer::{new_debouncer, Debouncer, NoCache};
 use notify::RecommendedWatcher;
 use notify::Watcher;
@@ -116,6 +117,9 @@ pub fn spawn(
     task::spawn_blocking(move || {
         tracing::debug!(%project_id, "file watcher started");
         let _runtime = tracing::span!(Level::INFO, "file monitor", %project_id ).entered();
+        // Listing virtual branches writes their state, so only changed contents are passed on.
+        let virtual_branches_path = git_dir.join("gitbutler").join(VIRTUAL_BRANCHES_FILE_NAME);
+        let mut last_virtual_branches = std::fs::read(&virtual_branches_path).ok();
 
         'outer: for result in notify_rx {
             let stats = tracing::span!(
@@ -164,7 +168,7 @@ pub fn spawn(
                             }
                         }
                     }
-                    let mut oplog_changed = false;
+                    let (mut oplog_changed, mut virtual_branches_changed) = (false, false);
                     let (mut stripped_git_paths, mut worktree_relative_paths) =
                         (HashSet::new(), HashSet::new());
                     for (file_path, kind) in classified_file_paths {
@@ -174,6 +178,9 @@ pub fn spawn(
                             FileKind::GitButlerOplog => {
                                 oplog_changed = true;
                             }
+                            FileKind::GitButlerVirtualBranches => {
+                                virtual_branches_changed = true;
+                            }
                             FileKind::Project | FileKind::Git => match file_path
                                 .strip_prefix(&worktree_path)
                             {
@@ -226,6 +233,17 @@ pub fn spawn(
                             break 'outer;
                         }
                     }
+                    if virtual_branches_changed {
+                        let contents = std::fs::read(&virtual_branches_path).ok();
+                        if contents != last_virtual_branches {
+                            last_virtual_branches = contents;
+                            let event = InternalEvent::GitButlerVirtualBranchesChange(project_id);
+                            if out.send(event).is_err() {
+                                tracing::info!("channel closed - stopping file watcher");
+                                break 'outer;
+                            }
+                        }
+                    }
                 }
             }
         }
@@ -265,6 +283,8 @@ enum FileKind {
     ProjectIgnored,
     /// GitButler oplog file (`.git/gitbutler/operations-log.toml`)
     GitButlerOplog,
+    /// GitButler virtual branches state file (`.git/gitbutler/virtual_branches.toml`)
+    GitButlerVirtualBranches,
 }
 
 fn classify_file(git_dir: &Path, file_path: &Path) -> FileKind {
@@ -278,6 +298,8 @@ fn classify_file(git_dir: &Path, file_path: &Path) -> FileKind {
             FileKind::Git
         } else if check_file_path == Path::new("gitbutler").join(OPLOG_FILE_NAME) {
             FileKind::GitButlerOplog
+        } else if check_file_path == Path::new("gitbutler").join(VIRTUAL_BRANCHES_FILE_NAME) {
+            FileKind::GitButlerVirtualBranches
         } else {
             FileKind::GitUninteresting
         }
@@ -69,6 +69,11 @@ impl Handler {
                 .await
                 .context("failed to handle gitbutler oplog change event"),
 
+            events::InternalEvent::GitButlerVirtualBranchesChange(project_id) => self
+                .calculate_virtual_branches(project_id)
+                .await
+                .context("failed to handle virtual branches state change event"),
+
             // This is only produced at the end of mutating Tauri commands to trigger a fresh state being served to the UI.
             events::InternalEvent::CalculateVirtualBranches(project_id) => self
                 .calculate_virtual_branches(project_id)